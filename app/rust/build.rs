@@ -7,6 +7,11 @@ fn main() {
     let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
     let dst = PathBuf::from(env::var_os("OUT_DIR").unwrap());
     let build = dst.join("build");
+    let android = env::var_os("CARGO_FEATURE_ANDROID").is_some();
+
+    // Host builds compile against a system V8 (see README)
+    let include =
+        env::var("V8_INCLUDE_DIR").unwrap_or_else(|_| format!("{}/libnode/include/node", dir));
 
     let mut cfg = cc::Build::new();
 
@@ -14,9 +19,27 @@ fn main() {
         .cpp(true)
         .flag_if_supported("-w")
         .flag_if_supported("-Wno-unused-parameter")
-        .include("libnode/include/node")
+        .include(include)
         .file("build/util/util.cpp")
-        .file("build/v8_jni/wrapper.cpp")
-        .file("build/api.cpp")
-        .compile("api");
+        .file("build/api.cpp");
+
+    if android {
+        cfg.define("RUST_JNI", None)
+            .file("build/v8_jni/wrapper.cpp");
+    }
+
+    cfg.compile("api");
+
+    // On Android, V8 is linked into `native-lib` by CMake.
+    if !android {
+        if let Ok(lib_dir) = env::var("V8_LIB_DIR") {
+            println!("cargo:rustc-link-search=native={}", lib_dir);
+        }
+        let lib = env::var("V8_LIB_NAME").unwrap_or_else(|_| "v8_monolith".to_owned());
+        println!("cargo:rustc-link-lib={}", lib);
+        println!("cargo:rustc-link-lib=dylib=stdc++");
+        println!("cargo:rerun-if-env-changed=V8_INCLUDE_DIR");
+        println!("cargo:rerun-if-env-changed=V8_LIB_DIR");
+        println!("cargo:rerun-if-env-changed=V8_LIB_NAME");
+    }
 }
@@ -1,6 +1,5 @@
 #include "api.h"
 #include "util/util.h"
-#include "v8_jni/wrapper.h"
 
 extern "C" void __unused deno_lock(void* d_) {
   auto* d = Deno::unwrap(d_);
@@ -26,16 +25,23 @@ extern "C" void __unused set_deno_resolver(void* d_) {
   // resolver
   Local<Function> resolver_ = get_function(
       context_->Global(), String::NewFromUtf8(d->isolate_, "resolve"));
+  Local<Function> rejecter_ = get_function(
+      context_->Global(), String::NewFromUtf8(d->isolate_, "reject"));
 
-  java_register_callback(d->isolate_, context_);
+#ifdef RUST_JNI
+  java_register_callback(&d->java_, d->isolate_, context_);
+#endif
   d->resolver_.Reset(d->isolate_, resolver_);
+  d->rejecter_.Reset(d->isolate_, rejecter_);
 }
 
+#ifdef RUST_JNI
 const char* __unused jStringToChar(JNIEnv* env, jstring name) {
   const char* str = env->GetStringUTFChars(name, 0);
   env->ReleaseStringUTFChars(name, str);
   return str;
 }
+#endif
 
 extern "C" Local<Value> __unused v8_function_callback_info_get(
     FunctionCallbackInfo<Value>* info, int32_t index) {
@@ -59,52 +65,103 @@ const char* ToCString(const String::Utf8Value& value) {
   return *value ? *value : "<string conversion failed>";
 }
 
-void Log(const FunctionCallbackInfo<Value>& args) {
+// $print(priority, message), priority as in android.util.Log
+void Print(const FunctionCallbackInfo<Value>& args) {
   auto d = Deno::unwrap(args.Data().As<External>()->Value());
-  lock_isolate(d->isolate_);
+  Local<Context> context_ = d->isolate_->GetCurrentContext();
 
-  int length = args.Length();
-  for (int i = 0; i < length; i++) {
-    String::Utf8Value value(d->isolate_, args[i]->ToString(d->isolate_));
-    adb_debug(ToCString(value));
-  }
+  int priority = args[0]->Int32Value(context_).FromMaybe(4);
+  String::Utf8Value message(d->isolate_, args[1]);
+  console_print(d->user_data_, priority, ToCString(message));
 }
 
 // exception
-void ExceptionString(TryCatch* try_catch) {
-  Isolate* isolate = Isolate::GetCurrent();
-  Local<Context> context = isolate->GetCurrentContext();
-
-  String::Utf8Value exception(isolate, try_catch->Exception());
-  const char* exception_string = ToCString(exception);
-  adb_debug(exception_string);
-
-  Handle<Message> message = try_catch->Message();
-  if (!message.IsEmpty()) {
-    String::Utf8Value filename(isolate,
-                               message->GetScriptOrigin().ResourceName());
-    const char* filename_string = ToCString(filename);
-    int line_number = message->GetLineNumber(context).ToChecked();
-    char s[1024];
-    // (filename):(line number)
-    sprintf(s, "%s:%i", filename_string, line_number);
-    adb_debug(s);
-  }
+Local<String> v8_str(Isolate* isolate_, const char* s) {
+  return String::NewFromUtf8(isolate_, s);
 }
 
-void Fetch(const FunctionCallbackInfo<Value>& args) {
-  assert(args[0]->IsString());
-  assert(args[1]->IsNumber());
+// Serializes an exception for the embedder, see JsError in error.rs
+std::string EncodeException(Isolate* isolate_, Local<Message> message,
+                            Local<Value> exception) {
+  Local<Context> context_ = isolate_->GetCurrentContext();
+  Local<Object> json = Object::New(isolate_);
+
+  json->Set(v8_str(isolate_, "message"), message->Get());
+  json->Set(v8_str(isolate_, "scriptName"), message->GetScriptResourceName());
+  json->Set(v8_str(isolate_, "line"),
+            Integer::New(isolate_, message->GetLineNumber(context_).FromMaybe(0)));
+  json->Set(v8_str(isolate_, "column"),
+            Integer::New(isolate_,
+                         message->GetStartColumn(context_).FromMaybe(0) + 1));
+
+  // Thrown errors carry the stack where they were created
+  Local<StackTrace> stack = Exception::GetStackTrace(exception);
+  if (stack.IsEmpty()) {
+    stack = message->GetStackTrace();
+  }
 
-  auto d = Deno::unwrap(args.Data().As<External>()->Value());
-  lock_isolate(d->isolate_);
+  Local<Array> frames = Array::New(isolate_);
+  if (!stack.IsEmpty()) {
+    for (int i = 0; i < stack->GetFrameCount(); i++) {
+      Local<StackFrame> frame = stack->GetFrame(i);
+      Local<Object> frame_ = Object::New(isolate_);
+      frame_->Set(v8_str(isolate_, "functionName"), frame->GetFunctionName());
+      frame_->Set(v8_str(isolate_, "scriptName"), frame->GetScriptName());
+      frame_->Set(v8_str(isolate_, "line"),
+                  Integer::New(isolate_, frame->GetLineNumber()));
+      frame_->Set(v8_str(isolate_, "column"),
+                  Integer::New(isolate_, frame->GetColumn()));
+      frames->Set(i, frame_);
+    }
+  }
+  json->Set(v8_str(isolate_, "frames"), frames);
 
-  Local<Context> context = d->isolate_->GetCurrentContext();
+  String::Utf8Value encoded(isolate_,
+                            JSON::Stringify(context_, json).ToLocalChecked());
+  return ToCString(encoded);
+}
 
-  String::Utf8Value url(d->isolate_, args[0]->ToString(d->isolate_));
-  uint32_t promise_id = args[1]->Uint32Value(context).ToChecked();
+// Keeps the exception caught by `try_catch` for deno_last_exception
+void HandleException(Deno* d, TryCatch* try_catch) {
+  if (try_catch->HasTerminated()) {
+    d->last_exception_ = "{\"message\":\"Script execution was terminated\"}";
+    return;
+  }
+  d->last_exception_ =
+      EncodeException(d->isolate_, try_catch->Message(), try_catch->Exception());
+}
 
-  fetch(d->user_data_, *url, promise_id);
+void PromiseRejectCallback(PromiseRejectMessage message) {
+  Local<Promise> promise = message.GetPromise();
+  Isolate* isolate_ = promise->GetIsolate();
+  auto d = static_cast<Deno*>(isolate_->GetData(0));
+  auto& pending = d->pending_rejections_;
+  // Identity hashes aren't unique, compare the promises themselves
+  auto it = std::find_if(pending.begin(), pending.end(),
+                         [&](const PendingRejection& rejection) {
+                           return rejection.promise == promise;
+                         });
+
+  switch (message.GetEvent()) {
+    case kPromiseRejectWithNoHandler: {
+      Local<Value> exception = message.GetValue();
+      Local<Message> message_ = Exception::CreateMessage(isolate_, exception);
+      if (it == pending.end()) {
+        it = pending.insert(pending.end(), PendingRejection());
+      }
+      it->promise.Reset(isolate_, promise);
+      it->reason.Reset(isolate_, exception);
+      it->json = EncodeException(isolate_, message_, exception);
+      break;
+    }
+    case kPromiseHandlerAddedAfterReject:
+      if (it != pending.end()) {
+        pending.erase(it);
+      }
+      break;
+    default:
+      break;
+  }
 }
 
 void HeapStatic(const FunctionCallbackInfo<Value>& args) {
@@ -129,17 +186,52 @@ void Toast(const FunctionCallbackInfo<Value>& args) {
   String::Utf8Value value(d->isolate_, args[0]->ToObject(d->isolate_));
 }
 
-void NewTimer(const FunctionCallbackInfo<Value>& args) {
-  assert(args[0]->IsUint32());  // promise_id
-  assert(args[1]->IsUint32());  // delay
+// Bytes of an ArrayBuffer(View), the UTF-8 of a string, or nothing
+class ByteView {
+public:
+  ByteView(Isolate* isolate, Local<Value> value) {
+    if (value->IsArrayBufferView()) {
+      auto view = Local<ArrayBufferView>::Cast(value);
+      auto data = static_cast<uint8_t*>(view->Buffer()->GetContents().Data());
+      data_ = data + view->ByteOffset();
+      length_ = view->ByteLength();
+    } else if (value->IsArrayBuffer()) {
+      auto ab = Local<ArrayBuffer>::Cast(value);
+      data_ = static_cast<uint8_t*>(ab->GetContents().Data());
+      length_ = ab->ByteLength();
+    } else if (value->IsString()) {
+      utf8_.reset(new String::Utf8Value(isolate, value));
+      data_ = reinterpret_cast<const uint8_t*>(**utf8_);
+      length_ = utf8_->length();
+    }
+  }
+
+  const uint8_t* data() const { return data_; }
+  size_t length() const { return length_; }
+
+private:
+  std::unique_ptr<String::Utf8Value> utf8_;
+  const uint8_t* data_ = nullptr;
+  size_t length_ = 0;
+};
+
+void Dispatch(const FunctionCallbackInfo<Value>& args) {
+  assert(args[0]->IsString());  // op
+  assert(args[1]->IsUint32());  // promise_id
 
   void* d_ = args.Data().As<External>()->Value();
   auto d = Deno::unwrap(d_);
   lock_isolate(d->isolate_);
 
   Local<Context> context = d->isolate_->GetCurrentContext();
-  d->recv_cb_(d->user_data_, args[0]->Uint32Value(context).ToChecked(),
-              args[1]->Uint32Value(context).ToChecked());
+  String::Utf8Value op(d->isolate_, args[0]);
+  uint32_t promise_id = args[1]->Uint32Value(context).ToChecked();
+
+  ByteView control(d->isolate_, args[2]);
+  ByteView zero_copy(d->isolate_, args[3]);
+
+  d->recv_cb_(d->user_data_, *op, promise_id, control.data(), control.length(),
+              zero_copy.data(), zero_copy.length());
 }
 
 /* do not remove */
@@ -180,29 +272,114 @@ extern "C" __unused void resolve(void* d_, uint32_t promise_id,
   resolver_->Call(context_, Null(d->isolate_), argc, argv);
 }
 
-extern "C" void __unused SendBuffer(const FunctionCallbackInfo<Value>& args) {
-  Isolate* isolate_ = args.GetIsolate();
+/* do not remove */
+extern "C" __unused void reject(void* d_, uint32_t promise_id,
+                                const char* error) {
+  auto d = Deno::unwrap(d_);
+  lock_isolate(d->isolate_);
+
+  Handle<Context> context_ = d->context_.Get(d->isolate_);
+  Context::Scope scope(context_);
+  Local<Function> rejecter_ = d->rejecter_.Get(d->isolate_);
+
+  // error is a JSON encoded { name, message, code }
+  const unsigned argc = 2;
+  Local<Value> argv[argc] = {
+      Number::New(d->isolate_, promise_id),
+      String::NewFromUtf8(d->isolate_, error),
+  };
+
+  rejecter_->Call(context_, Null(d->isolate_), argc, argv);
+}
+
+/* do not remove */
+extern "C" __unused void resolve_buffer(void* d_, uint32_t promise_id,
+                                        const char* value, const uint8_t* data,
+                                        size_t len) {
+  auto d = Deno::unwrap(d_);
+  lock_isolate(d->isolate_);
+
+  Handle<Context> context_ = d->context_.Get(d->isolate_);
+  Context::Scope scope(context_);
+  Local<Function> resolver_ = d->resolver_.Get(d->isolate_);
+
+  Local<ArrayBuffer> buffer = ArrayBuffer::New(d->isolate_, len);
+  memcpy(buffer->GetContents().Data(), data, len);
+
+  const unsigned argc = 3;
+  Local<Value> argv[argc] = {
+      Number::New(d->isolate_, promise_id),
+      String::NewFromUtf8(d->isolate_, value),
+      buffer,
+  };
+
+  resolver_->Call(context_, Null(d->isolate_), argc, argv);
+}
+
+// V8 itself is set up once per process, every Deno owns its isolate
+static std::once_flag v8_init_;
 
-  assert(args[0]->IsArrayBuffer());
-  assert(args[1]->IsFunction());
+// require(): resolves `specifier` through the embedder and compiles the
+// CommonJS wrapper, unless `cache` already holds the resolved filename
+void LoadModule(const FunctionCallbackInfo<Value>& args) {
+  auto d = Deno::unwrap(args.Data().As<External>()->Value());
+  Isolate* isolate_ = d->isolate_;
+  Local<Context> context_ = isolate_->GetCurrentContext();
+
+  String::Utf8Value specifier(isolate_, args[0]);
+  String::Utf8Value referrer(isolate_, args[1]);
+  Local<Object> cache = args[2]->ToObject(isolate_);
+
+  deno_source out = {nullptr, nullptr, nullptr};
+  d->load_cb_(d->user_data_, ToCString(specifier),
+              args[1]->IsString() ? ToCString(referrer) : nullptr, &out);
+  if (out.error != nullptr) {
+    isolate_->ThrowException(
+        Exception::Error(String::NewFromUtf8(isolate_, out.error)));
+    return;
+  }
+
+  Local<String> filename = String::NewFromUtf8(isolate_, out.filename);
+  Local<Object> result = Object::New(isolate_);
+  result->Set(String::NewFromUtf8(isolate_, "filename"), filename);
 
-  auto ab = Local<ArrayBuffer>::Cast(args[0]);
-  auto contents = ab->GetContents();
+  if (!cache->Has(context_, filename).FromMaybe(false)) {
+    std::string wrapped =
+        "(function (exports, require, module, __filename, __dirname) {";
+    wrapped += out.source;
+    wrapped += "\n})";
 
-  char* str = worker_send_bytes(contents.Data(), ab->ByteLength(), args[1]);
-  args.GetReturnValue().Set(String::NewFromUtf8(isolate_, str));
+    ScriptOrigin origin(filename);
+    Local<Script> script;
+    if (!Script::Compile(context_, String::NewFromUtf8(isolate_, wrapped.c_str()),
+                         &origin).ToLocal(&script)) {
+      return;
+    }
+    Local<Value> wrapper;
+    if (!script->Run(context_).ToLocal(&wrapper)) {
+      return;
+    }
+    result->Set(String::NewFromUtf8(isolate_, "wrapper"), wrapper);
+  }
+
+  args.GetReturnValue().Set(result);
 }
 
-extern "C" void* __unused deno_init(deno_recv_cb recv_cb, uint32_t uuid) {
-  V8::InitializeICU();
-  Platform* platform_ = platform::CreateDefaultPlatform();
-  V8::InitializePlatform(platform_);
-  V8::Initialize();
+extern "C" void* __unused deno_init(deno_recv_cb recv_cb, deno_load_cb load_cb,
+                                    uint32_t uuid) {
+  std::call_once(v8_init_, [] {
+    V8::InitializeICU();
+    Platform* platform_ = platform::CreateDefaultPlatform();
+    V8::InitializePlatform(platform_);
+    V8::Initialize();
+  });
 
   Isolate::CreateParams create_params;
   create_params.array_buffer_allocator =
       ArrayBuffer::Allocator::NewDefaultAllocator();
   Isolate* isolate_ = Isolate::New(create_params);
+  isolate_->SetCaptureStackTraceForUncaughtExceptions(true, 16);
+  isolate_->SetPromiseRejectCallback(PromiseRejectCallback);
 
   Isolate::Scope isolate_scope(isolate_);
   HandleScope scope(isolate_);
@@ -215,16 +392,16 @@ extern "C" void* __unused deno_init(deno_recv_cb recv_cb, uint32_t uuid) {
   Local<ObjectTemplate> global_ = ObjectTemplate::New(deno->isolate_);
 
   global_->Set(String::NewFromUtf8(isolate_, "$sendBuffer"),
-               FunctionTemplate::New(isolate_, SendBuffer, env_));
-
-  global_->Set(String::NewFromUtf8(isolate_, "$fetch"),
-               FunctionTemplate::New(isolate_, Fetch, env_));
+               FunctionTemplate::New(isolate_, worker_send_bytes, env_));
 
   global_->Set(String::NewFromUtf8(isolate_, "$static"),
                FunctionTemplate::New(isolate_, HeapStatic, env_));
 
-  global_->Set(String::NewFromUtf8(isolate_, "$newTimer"),
-               FunctionTemplate::New(isolate_, NewTimer, env_));
+  global_->Set(String::NewFromUtf8(isolate_, "$dispatch"),
+               FunctionTemplate::New(isolate_, Dispatch, env_));
+
+  global_->Set(String::NewFromUtf8(isolate_, "$loadModule"),
+               FunctionTemplate::New(isolate_, LoadModule, env_));
 
   global_->Set(String::NewFromUtf8(isolate_, "$toast"),
                FunctionTemplate::New(isolate_, Toast, env_));
@@ -232,29 +409,66 @@ extern "C" void* __unused deno_init(deno_recv_cb recv_cb, uint32_t uuid) {
   global_->Set(String::NewFromUtf8(isolate_, "$testFn"),
                FunctionTemplate::New(isolate_, test_fn, env_));
 
-  // console
-  Local<ObjectTemplate> console_ = ObjectTemplate::New(deno->isolate_);
+  // console is implemented in JS on top of $print
+  global_->Set(String::NewFromUtf8(isolate_, "$print"),
+               FunctionTemplate::New(isolate_, Print, env_));
 
-  console_->Set(String::NewFromUtf8(isolate_, "log"),
-                FunctionTemplate::New(isolate_, Log, env_));
-
-  global_->Set(String::NewFromUtf8(isolate_, "console"), console_);
-
-  JavaWrapper::Init(isolate_, global_);
+#ifdef RUST_JNI
+  JavaWrapper::Init(&deno->java_, isolate_, global_);
+#endif
 
   Local<Context> context_ = Context::New(isolate_, nullptr, global_);
-  JavaWrapper::SetContext(context_);
+#ifdef RUST_JNI
+  JavaWrapper::SetContext(&deno->java_, context_);
+#endif
 
   deno->ResetContext(context_);
   deno->ResetTemplate(global_);
   deno->recv_cb_ = recv_cb;
-
-  isolate_map_[deno->uuid_] = deno;
+  deno->load_cb_ = load_cb;
+  // Looked up by the module resolve callback, which has no data pointer
+  isolate_->SetData(0, deno);
 
   return deno->Into();
 }
 
-extern "C" void __unused eval_script(void* deno_, const char* name_s,
+extern "C" void __unused deno_delete(void* d_) {
+  auto deno = Deno::unwrap(d_);
+  {
+    Locker locker(deno->isolate_);
+    deno->resolver_.Reset();
+    deno->rejecter_.Reset();
+    deno->context_.Reset();
+    deno->global_.Reset();
+    for (auto& it : deno->modules_) {
+      it.second.handle.Reset();
+    }
+    deno->pending_rejections_.clear();
+#ifdef RUST_JNI
+    deno->java_.Reset();
+#endif
+  }
+  Isolate* isolate_ = deno->isolate_;
+  delete deno;
+  isolate_->Dispose();
+}
+
+// Runs `cb` with the isolate locked and its context entered
+extern "C" void __unused deno_with_scope(void* d_, void* data,
+                                         void (*cb)(void*)) {
+  auto deno = Deno::unwrap(d_);
+  lock_isolate(deno->isolate_);
+  Local<Context> context_ = deno->context_.Get(deno->isolate_);
+  Context::Scope context_scope(context_);
+  cb(data);
+}
+
+extern "C" void __unused deno_terminate(void* d_) {
+  // Thread-safe, the isolate must not be locked by the caller
+  Deno::unwrap(d_)->isolate_->TerminateExecution();
+}
+
+extern "C" bool __unused eval_script(void* deno_, const char* name_s,
                                      const char* script_s) {
   auto deno = Deno::unwrap(deno_);
   lock_isolate(deno->isolate_);
@@ -272,27 +486,260 @@ extern "C" void __unused eval_script(void* deno_, const char* name_s,
   MaybeLocal<Script> script = Script::Compile(context_, source, &origin);
 
   if (script.IsEmpty()) {
-    ExceptionString(&try_catch);
-    return;
+    HandleException(deno, &try_catch);
+    return false;
   }
 
   MaybeLocal<Value> result = script.ToLocalChecked()->Run(context_);
   if (result.IsEmpty()) {
-    ExceptionString(&try_catch);
-    return;
+    HandleException(deno, &try_catch);
+    return false;
+  }
+  return true;
+}
+
+// JSON of the exception which made the last eval_script or deno_mod_* call fail
+extern "C" const char* __unused deno_last_exception(void* d_) {
+  auto d = Deno::unwrap(d_);
+  return d->last_exception_.c_str();
+}
+
+// Fires the JS `unhandledrejection` event, returns true if a listener
+// called preventDefault()
+bool DispatchRejection(Deno* d, Local<Context> context_,
+                       Local<Promise> promise, Local<Value> reason) {
+  Local<Value> dispatch;
+  if (!context_->Global()
+           ->Get(context_, v8_str(d->isolate_, "dispatchRejection"))
+           .ToLocal(&dispatch) ||
+      !dispatch->IsFunction()) {
+    return false;
+  }
+
+  TryCatch try_catch(d->isolate_);
+  Local<Value> argv[] = {promise, reason};
+  Local<Value> result;
+  if (!dispatch.As<Function>()
+           ->Call(context_, context_->Global(), 2, argv)
+           .ToLocal(&result)) {
+    String::Utf8Value exception(d->isolate_, try_catch.Exception());
+    adb_debug(ToCString(exception));
+    return false;
+  }
+  return result->BooleanValue(context_).FromMaybe(false);
+}
+
+// Moves one of the unhandled rejections into deno_last_exception,
+// returns false if there is none. Rejections a JS listener prevented are
+// dropped.
+extern "C" bool __unused deno_take_rejection(void* d_) {
+  auto d = Deno::unwrap(d_);
+  lock_isolate(d->isolate_);
+
+  Local<Context> context_ = Local<Context>::New(d->isolate_, d->context_);
+  Context::Scope scope(context_);
+
+  // Listeners may reject more promises, so take one entry at a time
+  while (!d->pending_rejections_.empty()) {
+    auto it = d->pending_rejections_.begin();
+    Local<Promise> promise = it->promise.Get(d->isolate_);
+    Local<Value> reason = it->reason.Get(d->isolate_);
+    std::string json = it->json;
+    d->pending_rejections_.erase(it);
+
+    if (!DispatchRejection(d, context_, promise, reason)) {
+      d->last_exception_ = json;
+      return true;
+    }
+  }
+  return false;
+}
+
+// ES modules
+extern "C" int __unused deno_mod_new(void* d_, const char* name_s,
+                                     const char* source_s) {
+  auto d = Deno::unwrap(d_);
+  lock_isolate(d->isolate_);
+
+  Local<Context> context_ = Local<Context>::New(d->isolate_, d->context_);
+  Context::Scope scope(context_);
+
+  TryCatch try_catch(d->isolate_);
+
+  ScriptOrigin origin(String::NewFromUtf8(d->isolate_, name_s),
+                      Local<Integer>(), Local<Integer>(), Local<Boolean>(),
+                      Local<Integer>(), Local<Value>(), Local<Boolean>(),
+                      Local<Boolean>(), True(d->isolate_));
+  ScriptCompiler::Source source(
+      String::NewFromUtf8(d->isolate_, source_s, NewStringType::kNormal)
+          .ToLocalChecked(),
+      origin);
+
+  Local<Module> module;
+  if (!ScriptCompiler::CompileModule(d->isolate_, &source).ToLocal(&module)) {
+    HandleException(d, &try_catch);
+    return 0;
+  }
+
+  int id = d->next_module_id_++;
+  ModuleInfo& info = d->modules_[id];
+  info.name = name_s;
+  info.handle.Reset(d->isolate_, module);
+  for (int i = 0; i < module->GetModuleRequestsLength(); i++) {
+    String::Utf8Value request(d->isolate_, module->GetModuleRequest(i));
+    info.imports.emplace_back(ToCString(request));
   }
+  return id;
+}
+
+extern "C" size_t __unused deno_mod_imports_len(void* d_, int id) {
+  auto d = Deno::unwrap(d_);
+  return d->modules_[id].imports.size();
+}
+
+extern "C" const char* __unused deno_mod_imports_get(void* d_, int id,
+                                                      size_t index) {
+  auto d = Deno::unwrap(d_);
+  return d->modules_[id].imports[index].c_str();
+}
+
+extern "C" void __unused deno_mod_resolve(void* d_, int id,
+                                          const char* specifier, int child) {
+  auto d = Deno::unwrap(d_);
+  d->modules_[id].resolved[specifier] = child;
 }
 
-Deno* __unused lookup_deno_by_uuid(std::map<uint32_t, Deno*> isolate_map_,
-                                   uint32_t uuid) {
-  auto it = isolate_map_.find(uuid);
-  if (it != isolate_map_.end()) {
-    return it->second;
+static MaybeLocal<Module> ResolveModule(Local<Context> context,
+                                        Local<String> specifier,
+                                        Local<Module> referrer) {
+  Isolate* isolate_ = context->GetIsolate();
+  auto d = static_cast<Deno*>(isolate_->GetData(0));
+  String::Utf8Value specifier_(isolate_, specifier);
+
+  for (auto& it : d->modules_) {
+    ModuleInfo& info = it.second;
+    if (info.handle.Get(isolate_) != referrer) {
+      continue;
+    }
+    auto child = info.resolved.find(ToCString(specifier_));
+    if (child != info.resolved.end()) {
+      return d->modules_[child->second].handle.Get(isolate_);
+    }
+    break;
   }
-  return nullptr;
+
+  std::string message = "Cannot resolve module '";
+  message += ToCString(specifier_);
+  message += "'";
+  isolate_->ThrowException(
+      Exception::Error(String::NewFromUtf8(isolate_, message.c_str())));
+  return MaybeLocal<Module>();
+}
+
+extern "C" bool __unused deno_mod_evaluate(void* d_, int id) {
+  auto d = Deno::unwrap(d_);
+  lock_isolate(d->isolate_);
+
+  Local<Context> context_ = Local<Context>::New(d->isolate_, d->context_);
+  Context::Scope scope(context_);
+
+  TryCatch try_catch(d->isolate_);
+
+  Local<Module> module = d->modules_[id].handle.Get(d->isolate_);
+  if (module->GetStatus() == Module::kUninstantiated &&
+      !module->InstantiateModule(context_, ResolveModule).FromMaybe(false)) {
+    HandleException(d, &try_catch);
+    return false;
+  }
+
+  if (module->Evaluate(context_).IsEmpty()) {
+    HandleException(d, &try_catch);
+    return false;
+  }
+  return true;
 }
 
 // Utils for Rust represent
+extern "C" void* __unused isolate_get_data(uint32_t slot) {
+  return Isolate::GetCurrent()->GetData(slot);
+}
+
+extern "C" void __unused isolate_set_data(uint32_t slot, void* data) {
+  Isolate::GetCurrent()->SetData(slot, data);
+}
+
+extern "C" Global<Value>* __unused global_new(Local<Value> value) {
+  return new Global<Value>(Isolate::GetCurrent(), value);
+}
+
+// Empty once a weak global was collected
+extern "C" void __unused global_get(Local<Value>* out, Global<Value>* global) {
+  *out = global->Get(Isolate::GetCurrent());
+}
+
+extern "C" void __unused global_delete(Global<Value>* global) {
+  global->Reset();
+  delete global;
+}
+
+typedef void (*weak_cb)(void* data);
+
+struct WeakData {
+  Global<Value>* global;
+  weak_cb cb;
+  void* data;
+};
+
+static void WeakCollected(const WeakCallbackInfo<WeakData>& info) {
+  WeakData* weak = info.GetParameter();
+  // First pass callbacks must reset the handle and must not call into V8
+  weak->global->Reset();
+  weak->cb(weak->data);
+  delete weak;
+}
+
+// `cb` runs once the value is collected, unless the global is deleted first
+extern "C" void __unused global_set_weak(Global<Value>* global, void* data,
+                                         weak_cb cb) {
+  global->SetWeak(new WeakData{global, cb, data}, WeakCollected,
+                  WeakCallbackType::kParameter);
+}
+
+// Deletes a weak global which was not collected, `cb` won't run
+extern "C" void __unused global_delete_weak(Global<Value>* global) {
+  delete global->ClearWeak<WeakData>();
+  global_delete(global);
+}
+
+// Collects every unreachable value, their weak callbacks run before it returns
+extern "C" void __unused isolate_collect_garbage() {
+  Isolate::GetCurrent()->LowMemoryNotification();
+}
+
+extern "C" bool __unused isolate_entered() {
+  return Isolate::GetCurrent() != nullptr;
+}
+
+// Runs `cb` inside a HandleScope, the locals it creates are released after it
+extern "C" void __unused with_handle_scope(void* data, void (*cb)(void*)) {
+  HandleScope handle_scope(Isolate::GetCurrent());
+  cb(data);
+}
+
+// Like with_handle_scope, `cb` may move one local to the outer scope with
+// escape_handle
+extern "C" void __unused with_escapable_handle_scope(
+    void* data, void (*cb)(void*, EscapableHandleScope*)) {
+  EscapableHandleScope handle_scope(Isolate::GetCurrent());
+  cb(data, &handle_scope);
+}
+
+extern "C" void __unused escape_handle(Local<Value>* out,
+                                       EscapableHandleScope* scope,
+                                       Local<Value> value) {
+  *out = scope->Escape(value);
+}
+
 extern "C" void __unused upcast_value(Local<Value>* source,
                                       Local<Value>* dest) {
   // upcast
@@ -315,6 +762,18 @@ extern "C" void __unused new_array(Local<Array>* out, uint32_t length) {
   *out = Array::New(isolate_, length);
 }
 
+extern "C" uint32_t __unused array_length(Local<Array> array) {
+  return array->Length();
+}
+
+extern "C" void __unused new_boolean(Local<Boolean>* out, bool value) {
+  *out = Boolean::New(Isolate::GetCurrent(), value);
+}
+
+extern "C" bool __unused boolean_value(Local<Boolean> value) {
+  return value->Value();
+}
+
 extern "C" bool __unused mem_same_handle(Local<Value> v1, Local<Value> v2) {
   return v1 == v2;
 }
@@ -324,11 +783,144 @@ set_return_value(const FunctionCallbackInfo<Value>& args, Local<Value> value) {
   args.GetReturnValue().Set(value);
 }
 
+// Copies `data`, V8 owns the new backing store
 extern "C" void __unused new_array_buffer(Local<ArrayBuffer>* out, void* data,
                                           size_t byte_length) {
   Isolate* isolate_ = Isolate::GetCurrent();
+  *out = ArrayBuffer::New(isolate_, byte_length);
+  if (byte_length > 0) {
+    memcpy((*out)->GetContents().Data(), data, byte_length);
+  }
+}
+
+typedef void (*external_free_cb)(void* data, size_t byte_length, void* hint);
+
+// Frees the memory of an external ArrayBuffer once it is collected. The
+// buffer can't be touched from the weak callback, so its memory is kept here.
+struct ExternalBuffer {
+  Global<ArrayBuffer> handle;
+  void* data;
+  size_t byte_length;
+  external_free_cb free_cb;
+  void* hint;
+};
+
+static void ExternalBufferCollected(
+    const WeakCallbackInfo<ExternalBuffer>& info) {
+  ExternalBuffer* external = info.GetParameter();
+  external->handle.Reset();
+  external->free_cb(external->data, external->byte_length, external->hint);
+  delete external;
+}
+
+// Wraps memory owned by the embedder, `free_cb` releases it when the buffer is
+// garbage collected
+extern "C" void __unused new_external_array_buffer(Local<ArrayBuffer>* out,
+                                                   void* data,
+                                                   size_t byte_length,
+                                                   external_free_cb free_cb,
+                                                   void* hint) {
+  Isolate* isolate_ = Isolate::GetCurrent();
   *out = ArrayBuffer::New(isolate_, data, byte_length,
-                          ArrayBufferCreationMode::kInternalized);
+                          ArrayBufferCreationMode::kExternalized);
+  auto external = new ExternalBuffer{Global<ArrayBuffer>(isolate_, *out), data,
+                                     byte_length, free_cb, hint};
+  external->handle.SetWeak(external, ExternalBufferCollected,
+                           WeakCallbackType::kParameter);
+}
+
+extern "C" void __unused array_buffer_contents(Local<ArrayBuffer> buffer,
+                                               void** data, size_t* len) {
+  ArrayBuffer::Contents contents = buffer->GetContents();
+  *data = contents.Data();
+  *len = contents.ByteLength();
+}
+
+// Must match `TypedArrayKind` in typed_array.rs
+enum TypedArrayKind {
+  kInt8 = 0,
+  kUint8,
+  kUint8Clamped,
+  kInt16,
+  kUint16,
+  kInt32,
+  kUint32,
+  kFloat32,
+  kFloat64,
+};
+
+extern "C" bool __unused new_typed_array(Local<Value>* out, int kind,
+                                         Local<ArrayBuffer> buffer,
+                                         size_t byte_offset, size_t length) {
+  switch (kind) {
+    case kInt8:
+      *out = Int8Array::New(buffer, byte_offset, length);
+      return true;
+    case kUint8:
+      *out = Uint8Array::New(buffer, byte_offset, length);
+      return true;
+    case kUint8Clamped:
+      *out = Uint8ClampedArray::New(buffer, byte_offset, length);
+      return true;
+    case kInt16:
+      *out = Int16Array::New(buffer, byte_offset, length);
+      return true;
+    case kUint16:
+      *out = Uint16Array::New(buffer, byte_offset, length);
+      return true;
+    case kInt32:
+      *out = Int32Array::New(buffer, byte_offset, length);
+      return true;
+    case kUint32:
+      *out = Uint32Array::New(buffer, byte_offset, length);
+      return true;
+    case kFloat32:
+      *out = Float32Array::New(buffer, byte_offset, length);
+      return true;
+    case kFloat64:
+      *out = Float64Array::New(buffer, byte_offset, length);
+      return true;
+    default:
+      return false;
+  }
+}
+
+extern "C" bool __unused value_is_typed_array(Local<Value> value, int kind) {
+  switch (kind) {
+    case kInt8:
+      return value->IsInt8Array();
+    case kUint8:
+      return value->IsUint8Array();
+    case kUint8Clamped:
+      return value->IsUint8ClampedArray();
+    case kInt16:
+      return value->IsInt16Array();
+    case kUint16:
+      return value->IsUint16Array();
+    case kInt32:
+      return value->IsInt32Array();
+    case kUint32:
+      return value->IsUint32Array();
+    case kFloat32:
+      return value->IsFloat32Array();
+    case kFloat64:
+      return value->IsFloat64Array();
+    default:
+      return false;
+  }
+}
+
+// The bytes a view covers, within its buffer
+extern "C" void __unused array_buffer_view_contents(Local<ArrayBufferView> view,
+                                                    void** data, size_t* len) {
+  ArrayBuffer::Contents contents = view->Buffer()->GetContents();
+  *data = static_cast<uint8_t*>(contents.Data()) + view->ByteOffset();
+  *len = view->ByteLength();
+}
+
+extern "C" void __unused array_buffer_view_buffer(Local<ArrayBuffer>* out,
+                                                  Local<ArrayBufferView> view) {
+  *out = view->Buffer();
 }
 
 extern "C" bool __unused function_call(Local<Value>* out, Local<Function> fun,
@@ -368,11 +960,28 @@ extern "C" void __unused new_utf8_string(Local<String>* out,
   maybe_local.ToLocal(out);
 }
 
+// Length of the string once converted to UTF-8, without a terminator
+extern "C" size_t __unused string_utf8_length(Local<String> str) {
+  return str->Utf8Length(Isolate::GetCurrent());
+}
+
+// Lone surrogates are replaced with U+FFFD, so the output is always valid UTF-8
+extern "C" size_t __unused string_write_utf8(Local<String> str, uint8_t* buf,
+                                             size_t capacity) {
+  return str->WriteUtf8(Isolate::GetCurrent(), (char*) buf, capacity, nullptr,
+                        String::NO_NULL_TERMINATION |
+                            String::REPLACE_INVALID_UTF8);
+}
+
 extern "C" void __unused new_object(Local<Object>* out) {
   Isolate* isolate_ = Isolate::GetCurrent();
   *out = Object::New(isolate_);
 }
 
+extern "C" void __unused context_global(Local<Object>* out) {
+  *out = Isolate::GetCurrent()->GetCurrentContext()->Global();
+}
+
 extern "C" bool __unused object_set(bool* out, Local<Object> obj,
                                     Local<Value> key, Local<Value> val) {
   Local<Context> context_ = Isolate::GetCurrent()->GetCurrentContext();
@@ -423,6 +1032,74 @@ extern "C" bool __unused object_string_get(Local<Value>* out, Local<Object> obj,
   return maybe_local.ToLocal(out);
 }
 
+extern "C" bool __unused object_get(Local<Value>* out, Local<Object> obj,
+                                    Local<Value> key) {
+  Local<Context> context_ = Isolate::GetCurrent()->GetCurrentContext();
+  MaybeLocal<Value> maybe_local = obj->Get(context_, key);
+  return maybe_local.ToLocal(out);
+}
+
+extern "C" bool __unused object_index_get(Local<Value>* out, Local<Object> obj,
+                                          uint32_t index) {
+  Local<Context> context_ = Isolate::GetCurrent()->GetCurrentContext();
+  MaybeLocal<Value> maybe_local = obj->Get(context_, index);
+  return maybe_local.ToLocal(out);
+}
+
+extern "C" bool __unused object_has(bool* out, Local<Object> obj,
+                                    Local<Value> key) {
+  Local<Context> context_ = Isolate::GetCurrent()->GetCurrentContext();
+  Maybe<bool> maybe = obj->Has(context_, key);
+  return maybe.IsJust() && (*out = maybe.FromJust(), true);
+}
+
+extern "C" bool __unused object_index_has(bool* out, Local<Object> obj,
+                                          uint32_t index) {
+  Local<Context> context_ = Isolate::GetCurrent()->GetCurrentContext();
+  Maybe<bool> maybe = obj->Has(context_, index);
+  return maybe.IsJust() && (*out = maybe.FromJust(), true);
+}
+
+extern "C" bool __unused object_string_has(bool* out, Local<Object> obj,
+                                           const uint8_t* data, uint32_t len) {
+  Local<String> key;
+  if (!string_get(&key, data, len)) {
+    return false;
+  }
+  return object_has(out, obj, key);
+}
+
+extern "C" bool __unused object_delete(bool* out, Local<Object> obj,
+                                       Local<Value> key) {
+  Local<Context> context_ = Isolate::GetCurrent()->GetCurrentContext();
+  Maybe<bool> maybe = obj->Delete(context_, key);
+  return maybe.IsJust() && (*out = maybe.FromJust(), true);
+}
+
+extern "C" bool __unused object_index_delete(bool* out, Local<Object> obj,
+                                             uint32_t index) {
+  Local<Context> context_ = Isolate::GetCurrent()->GetCurrentContext();
+  Maybe<bool> maybe = obj->Delete(context_, index);
+  return maybe.IsJust() && (*out = maybe.FromJust(), true);
+}
+
+extern "C" bool __unused object_string_delete(bool* out, Local<Object> obj,
+                                              const uint8_t* data,
+                                              uint32_t len) {
+  Local<String> key;
+  if (!string_get(&key, data, len)) {
+    return false;
+  }
+  return object_delete(out, obj, key);
+}
+
+extern "C" bool __unused object_own_property_names(Local<Array>* out,
+                                                   Local<Object> obj) {
+  Local<Context> context_ = Isolate::GetCurrent()->GetCurrentContext();
+  MaybeLocal<Array> maybe_local = obj->GetOwnPropertyNames(context_);
+  return maybe_local.ToLocal(out);
+}
+
 extern "C" void __unused null_value(Local<Primitive>* out) {
   *out = Null(Isolate::GetCurrent());
 }
@@ -431,6 +1108,46 @@ extern "C" void __unused undefined_value(Local<Primitive>* out) {
   *out = Undefined(Isolate::GetCurrent());
 }
 
+extern "C" bool __unused value_is_string(Local<Value> value) {
+  return value->IsString();
+}
+
+extern "C" bool __unused value_is_number(Local<Value> value) {
+  return value->IsNumber();
+}
+
+extern "C" bool __unused value_is_object(Local<Value> value) {
+  return value->IsObject();
+}
+
+extern "C" bool __unused value_is_array(Local<Value> value) {
+  return value->IsArray();
+}
+
+extern "C" bool __unused value_is_function(Local<Value> value) {
+  return value->IsFunction();
+}
+
+extern "C" bool __unused value_is_promise(Local<Value> value) {
+  return value->IsPromise();
+}
+
+extern "C" bool __unused value_is_array_buffer(Local<Value> value) {
+  return value->IsArrayBuffer();
+}
+
+extern "C" bool __unused value_is_boolean(Local<Value> value) {
+  return value->IsBoolean();
+}
+
+extern "C" bool __unused value_is_null(Local<Value> value) {
+  return value->IsNull();
+}
+
+extern "C" bool __unused value_is_undefined(Local<Value> value) {
+  return value->IsUndefined();
+}
+
 extern "C" void new_function(Local<Function>* out, FunctionCallback cb) {
   Isolate* isolate_ = Isolate::GetCurrent();
   MaybeLocal<Function> maybe_local =
@@ -438,6 +1155,39 @@ extern "C" void new_function(Local<Function>* out, FunctionCallback cb) {
   maybe_local.ToLocal(out);
 }
 
+typedef void (*closure_free_cb)(void* closure);
+
+// Frees the Rust closure of a function once the function is collected
+struct ClosureData {
+  Global<Function> handle;
+  closure_free_cb free_cb;
+  void* closure;
+};
+
+static void ClosureCollected(const WeakCallbackInfo<ClosureData>& info) {
+  ClosureData* data = info.GetParameter();
+  data->handle.Reset();
+  data->free_cb(data->closure);
+  delete data;
+}
+
+// `cb` finds `closure` with `callback_info_data`
+extern "C" void __unused new_closure_function(Local<Function>* out,
+                                              FunctionCallback cb,
+                                              void* closure,
+                                              closure_free_cb free_cb) {
+  Isolate* isolate_ = Isolate::GetCurrent();
+  MaybeLocal<Function> maybe_local = Function::New(
+      isolate_->GetCurrentContext(), cb, External::New(isolate_, closure));
+  if (!maybe_local.ToLocal(out)) {
+    free_cb(closure);
+    return;
+  }
+  auto data = new ClosureData{Global<Function>(isolate_, *out), free_cb,
+                              closure};
+  data->handle.SetWeak(data, ClosureCollected, WeakCallbackType::kParameter);
+}
+
 extern "C" void __unused promise_then(Local<Promise>* promise,
                                       Local<Function> handler) {
   Isolate* isolate_ = Isolate::GetCurrent();
@@ -448,10 +1198,140 @@ extern "C" void __unused promise_then(Local<Promise>* promise,
 
 extern "C" void callback_info_get(const FunctionCallbackInfo<Value>& args,
                                   uint32_t index, Local<Value>* out) {
-  assert(args.Length() >= index);
+  // Undefined past the last argument
   *out = args[index];
 }
 
+extern "C" void* __unused
+callback_info_data(const FunctionCallbackInfo<Value>& args) {
+  return args.Data().As<External>()->Value();
+}
+
+static Local<String> error_message(const uint8_t* data, uint32_t len) {
+  return String::NewFromUtf8(Isolate::GetCurrent(), (const char*) data,
+                             NewStringType::kNormal, len)
+      .ToLocalChecked();
+}
+
+extern "C" void __unused throw_error(const uint8_t* data, uint32_t len) {
+  Isolate::GetCurrent()->ThrowException(
+      Exception::Error(error_message(data, len)));
+}
+
+extern "C" void __unused throw_type_error(const uint8_t* data, uint32_t len) {
+  Isolate::GetCurrent()->ThrowException(
+      Exception::TypeError(error_message(data, len)));
+}
+
+extern "C" uint32_t callback_info_length(
+    const FunctionCallbackInfo<Value>& args) {
+  return args.Length();
+}
+
+extern "C" void __unused callback_info_this(
+    const FunctionCallbackInfo<Value>& args, Local<Object>* out) {
+  *out = args.This();
+}
+
+extern "C" bool __unused
+callback_info_is_construct_call(const FunctionCallbackInfo<Value>& args) {
+  return args.IsConstructCall();
+}
+
+// Instances get two internal fields, the wrapped native value and `kClassTag`
+extern "C" void __unused new_class_template(Local<FunctionTemplate>* out,
+                                            const uint8_t* name, uint32_t len,
+                                            FunctionCallback constructor,
+                                            void* data) {
+  Isolate* isolate_ = Isolate::GetCurrent();
+  *out = FunctionTemplate::New(isolate_, constructor,
+                               External::New(isolate_, data));
+  (*out)->SetClassName(String::NewFromUtf8(isolate_, (const char*) name,
+                                           NewStringType::kNormal, len)
+                           .ToLocalChecked());
+  (*out)->InstanceTemplate()->SetInternalFieldCount(2);
+}
+
+// The signature makes V8 throw when `this` is not an instance of the class
+static Local<FunctionTemplate> class_member(Local<FunctionTemplate> tmpl,
+                                            FunctionCallback cb, void* data) {
+  if (cb == nullptr) {
+    return Local<FunctionTemplate>();
+  }
+  Isolate* isolate_ = Isolate::GetCurrent();
+  return FunctionTemplate::New(isolate_, cb, External::New(isolate_, data),
+                               Signature::New(isolate_, tmpl));
+}
+
+extern "C" void __unused class_template_set_method(
+    Local<FunctionTemplate> tmpl, const uint8_t* name, uint32_t len,
+    FunctionCallback cb, void* data) {
+  tmpl->PrototypeTemplate()->Set(
+      String::NewFromUtf8(Isolate::GetCurrent(), (const char*) name,
+                          NewStringType::kNormal, len)
+          .ToLocalChecked(),
+      class_member(tmpl, cb, data));
+}
+
+// `setter` may be null for a read-only property
+extern "C" void __unused class_template_set_accessor(
+    Local<FunctionTemplate> tmpl, const uint8_t* name, uint32_t len,
+    FunctionCallback getter, void* getter_data, FunctionCallback setter,
+    void* setter_data) {
+  tmpl->PrototypeTemplate()->SetAccessorProperty(
+      String::NewFromUtf8(Isolate::GetCurrent(), (const char*) name,
+                          NewStringType::kNormal, len)
+          .ToLocalChecked(),
+      class_member(tmpl, getter, getter_data),
+      class_member(tmpl, setter, setter_data));
+}
+
+extern "C" void __unused class_template_get_function(
+    Local<Function>* out, Local<FunctionTemplate> tmpl) {
+  Isolate* isolate_ = Isolate::GetCurrent();
+  tmpl->GetFunction(isolate_->GetCurrentContext()).ToLocal(out);
+}
+
+// Marks objects wrapped by `object_wrap`, other objects with internal fields, like
+// JavaWrapper instances, hold something else in field 0
+static int kClassTag;
+
+// Frees the native value of a wrapped object once the object is collected
+struct WrappedObject {
+  Global<Object> handle;
+  closure_free_cb free_cb;
+  void* value;
+};
+
+static void WrappedObjectCollected(
+    const WeakCallbackInfo<WrappedObject>& info) {
+  WrappedObject* wrapped = info.GetParameter();
+  wrapped->handle.Reset();
+  wrapped->free_cb(wrapped->value);
+  delete wrapped;
+}
+
+extern "C" void __unused object_wrap(Local<Object> object, void* value,
+                                     closure_free_cb free_cb) {
+  Isolate* isolate_ = Isolate::GetCurrent();
+  object->SetAlignedPointerInInternalField(0, value);
+  object->SetAlignedPointerInInternalField(1, &kClassTag);
+  auto wrapped = new WrappedObject{Global<Object>(isolate_, object), free_cb,
+                                   value};
+  wrapped->handle.SetWeak(wrapped, WrappedObjectCollected,
+                          WeakCallbackType::kParameter);
+}
+
+// Null for objects not wrapped by `object_wrap`
+extern "C" void* __unused object_unwrap(Local<Object> object) {
+  if (object->InternalFieldCount() < 2 ||
+      object->GetAlignedPointerFromInternalField(1) != &kClassTag) {
+    return nullptr;
+  }
+  return object->GetAlignedPointerFromInternalField(0);
+}
+
+#ifdef RUST_JNI
 extern "C" void attach_current_thread(JNIEnv** env) {
   int res = vm->GetEnv(reinterpret_cast<void**>(&(*env)), JNI_VERSION_1_6);
   if (res != JNI_OK) {
@@ -460,4 +1340,5 @@ extern "C" void attach_current_thread(JNIEnv** env) {
       return;
     }
   }
-}
\ No newline at end of file
+}
+#endif
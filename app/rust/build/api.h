@@ -5,16 +5,29 @@
 #endif
 
 #include <libplatform/libplatform.h>
+#include <algorithm>
 #include <cassert>
 #include <cstdio>
 #include <features.h>
 #include <iostream>
+#ifdef RUST_JNI
 #include <jni.h>
+#include "v8_jni/wrapper.h"
+#endif
+#include <cstring>
 #include <map>
+#include <memory>
+#include <mutex>
 #include <string>
 #include <thread>
+#include <vector>
 #include <v8.h>
 
+// bionic's <sys/cdefs.h> provides this, glibc does not
+#ifndef __unused
+#define __unused __attribute__((unused))
+#endif
+
 #define lock_isolate(isolate_)            \
   Locker locker(isolate_);                \
   Isolate::Scope isolate_scope(isolate_); \
@@ -23,38 +36,82 @@
 using namespace v8;
 
 using ResolverPersistent = Persistent<Promise::Resolver>;
-typedef void (* deno_recv_cb)(void* data, uint32_t promise_id, uint32_t delay);
+typedef void (* deno_recv_cb)(void* data, const char* op, uint32_t promise_id,
+                              const uint8_t* control, size_t len,
+                              const uint8_t* zero_copy, size_t zero_copy_len);
+
+// Filled by the embedder when a module is requested, `error` is set on failure
+struct deno_source {
+  const char* filename;
+  const char* source;
+  const char* error;
+};
 
+typedef void (* deno_load_cb)(void* data, const char* specifier,
+                              const char* referrer, deno_source* out);
+
+#ifdef RUST_JNI
 // NDK vm instance
 static JavaVM* vm;
+#endif
 
 // Rust bridge
 extern "C" {
 void adb_debug(const char*);
-void fetch(void* data, const char*, uint32_t);
+void console_print(void* data, int priority, const char* message);
 void test_fn(const FunctionCallbackInfo<Value>&);
-char* worker_send_bytes(void*, size_t, Local<Value> val);
+void worker_send_bytes(const FunctionCallbackInfo<Value>&);
+#ifdef RUST_JNI
 void attach_current_thread(JNIEnv** env);
 void register_vm(JavaVM* _vm) { vm = _vm; }
 JavaVM* __unused get_java_vm() { return vm; }
+#endif
 }
 
+// An ES module compiled by deno_mod_new
+struct ModuleInfo {
+  std::string name;
+  Persistent<Module> handle;
+  std::vector<std::string> imports;
+  // import specifier -> module id, filled by deno_mod_resolve
+  std::map<std::string, int> resolved;
+};
+
+// A promise rejected without a handler, reported after the current task
+struct PendingRejection {
+  Global<Promise> promise;
+  Global<Value> reason;
+  std::string json;
+};
+
 class Deno {
 public:
   Isolate* isolate_;
   Persistent<Context> context_;
   Persistent<ObjectTemplate> global_;
   Persistent<Function> resolver_;
+  Persistent<Function> rejecter_;
   Locker* locker_;
+#ifdef RUST_JNI
   JNIEnv* env_;
+  JavaState java_;
+#endif
 
   uint32_t uuid_;
   void* user_data_;
   deno_recv_cb recv_cb_;
+  deno_load_cb load_cb_;
+  std::map<int, ModuleInfo> modules_;
+  int next_module_id_ = 1;
+  std::string last_exception_;
+  // rejections not handled yet, oldest first
+  std::vector<PendingRejection> pending_rejections_;
 
   explicit Deno(Isolate* isolate, uint32_t uuid)
       : isolate_(isolate), uuid_(uuid) {
+#ifdef RUST_JNI
     attach_current_thread(&this->env_);
+#endif
   }
 
   Deno(Isolate* isolate, Local<Context> context, Local<ObjectTemplate> global)
@@ -63,7 +120,11 @@ public:
     this->global_.Reset(this->isolate_, global);
   }
 
-  ~Deno() { vm->DetachCurrentThread(); }
+  ~Deno() {
+#ifdef RUST_JNI
+    vm->DetachCurrentThread();
+#endif
+  }
 
   void ResetContext(Local<Context> c) {
     this->context_.Reset(this->isolate_, c);
@@ -82,8 +143,6 @@ public:
   static Deno* unwrap(void* d_) { return reinterpret_cast<Deno*>(d_); }
 };
 
-static std::map<uint32_t, Deno*> isolate_map_;
-
 #ifdef __cplusplus
 #endif
 
@@ -7,6 +7,7 @@ string_t _new_string_t(const std::string& s) {
   return st;
 }
 
+#ifdef RUST_JNI
 value_t _new_int_value(uint32_t val) {
   value_t v;
   v.data.i = val;
@@ -20,6 +21,7 @@ value_t _new_string_value(char* bytes, int length) {
   v.t = 3;
   return v;
 }
+#endif
 
 std::string v8str(Local<String> input) {
   Isolate* isolate = Isolate::GetCurrent();
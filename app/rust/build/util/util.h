@@ -3,20 +3,23 @@
 
 #include "v8.h"
 #include <iostream>
+#ifdef RUST_JNI
 #include <jni.h>
+#endif
 #include <string.h>
 #include <string>
 
 using namespace v8;
 using namespace std;
 
-typedef void (* JNIClosure)(void*, jlong, jlong);
-
 typedef struct {
   const uint8_t* ptr;
   uint32_t len;
 } string_t;
 
+#ifdef RUST_JNI
+typedef void (* JNIClosure)(void*, jlong, jlong);
+
 typedef union data_t {
   int32_t i;
   jlong s;
@@ -26,21 +29,26 @@ typedef struct value_t {
   data_t data;
   uint8_t t;
 } value_t;
+#endif
 
 using namespace v8;
 using namespace std;
 
 extern "C" {
+#ifdef RUST_JNI
 jlong _rust_new_string(const char*);
 jlong new_instance(string_t, const value_t*, uint32_t);
 void instance_call_args(jlong, jlong, const value_t*, uint32_t,
                         const FunctionCallbackInfo<Value>&);
+#endif
 void adb_debug(const char*);
 }
 
 string_t _new_string_t(const std::string& s);
+#ifdef RUST_JNI
 value_t _new_int_value(uint32_t val);
 value_t _new_string_value(char*, int);
+#endif
 
 std::string v8str(Local<String> input);
 string_t v8string_t(Local<Value> input);
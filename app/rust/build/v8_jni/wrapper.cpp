@@ -1,12 +1,8 @@
 #include "wrapper.h"
 #include <unistd.h>
 
-Persistent<FunctionTemplate> JavaWrapper::constructor_;
-Persistent<Function> JavaWrapper::registerUITask_;
-Persistent<Function> JavaWrapper::resolverUITask_;
-Persistent<Context> JavaWrapper::resolverContext_;
-
-void JavaWrapper::Init(Isolate* isolate_, Local<ObjectTemplate> exports) {
+void JavaWrapper::Init(JavaState* state, Isolate* isolate_,
+                       Local<ObjectTemplate> exports) {
   Local<FunctionTemplate> tpl = FunctionTemplate::New(isolate_, New);
 
   tpl->SetClassName(String::NewFromUtf8(isolate_, "Java"));
@@ -18,15 +14,15 @@ void JavaWrapper::Init(Isolate* isolate_, Local<ObjectTemplate> exports) {
   proto->Set(String::NewFromUtf8(isolate_, "isMethod"),
              FunctionTemplate::New(isolate_, IsMethod));
 
-  constructor_.Reset(isolate_, tpl);
+  state->constructor_.Reset(isolate_, tpl);
   exports->Set(String::NewFromUtf8(isolate_, "Java"), tpl);
 
   exports->Set(String::NewFromUtf8(isolate_, "$invokeJavaFn"),
                FunctionTemplate::New(isolate_, InvokeJavaFunction));
 }
 
-void JavaWrapper::SetContext(Local<Context> context_) {
-  resolverContext_.Reset(Isolate::GetCurrent(), context_);
+void JavaWrapper::SetContext(JavaState* state, Local<Context> context_) {
+  state->resolverContext_.Reset(Isolate::GetCurrent(), context_);
 }
 
 void JavaWrapper::New(const FunctionCallbackInfo<Value>& info) {
@@ -116,22 +112,24 @@ void JavaWrapper::InvokeJavaFunction(const FunctionCallbackInfo<Value>& info) {
   instance_call_args(wrapper->ptr_, name, args, argc, info);
 }
 
-void JavaWrapper::CallbackRegister(Isolate* isolate_, Local<Context> context) {
+void JavaWrapper::CallbackRegister(JavaState* state, Isolate* isolate_,
+                                   Local<Context> context) {
   Local<Object> global = context->Global();
 
-  resolverContext_.Reset(isolate_, context);
+  state->resolverContext_.Reset(isolate_, context);
 
   Local<Function> register_ =
       get_function(global, String::NewFromUtf8(isolate_, "registerUITask"));
-  registerUITask_.Reset(isolate_, register_);
+  state->registerUITask_.Reset(isolate_, register_);
 
   Local<Function> resolver_ =
       get_function(global, String::NewFromUtf8(isolate_, "resolverUITask"));
-  resolverUITask_.Reset(isolate_, resolver_);
+  state->resolverUITask_.Reset(isolate_, resolver_);
 }
 
 JavaWrapper::~JavaWrapper() { adb_debug("Destroyed"); }
 
-void java_register_callback(Isolate* isolate_, Local<Context> context) {
-  JavaWrapper::CallbackRegister(isolate_, context);
+void java_register_callback(JavaState* state, Isolate* isolate_,
+                            Local<Context> context) {
+  JavaWrapper::CallbackRegister(state, isolate_, context);
 }
\ No newline at end of file
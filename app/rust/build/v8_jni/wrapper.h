@@ -17,18 +17,34 @@ bool is_method(jlong, string_t);
 bool is_field(jlong, string_t);
 }
 
-void java_register_callback(Isolate* isolate_, Local<Context> context);
+// Handles of the Java bridge, one per isolate and owned by its Deno
+struct JavaState {
+  Persistent<FunctionTemplate> constructor_;
+  Persistent<Context> resolverContext_;
+  Persistent<Function> registerUITask_;
+  Persistent<Function> resolverUITask_;
+
+  // Must run with the isolate locked
+  void Reset() {
+    constructor_.Reset();
+    resolverContext_.Reset();
+    registerUITask_.Reset();
+    resolverUITask_.Reset();
+  }
+};
+
+void java_register_callback(JavaState* state, Isolate* isolate_,
+                            Local<Context> context);
 
 class JavaWrapper : public rust::ObjectWrap {
 public:
-  static void Init(Isolate* isolate_, Local<ObjectTemplate> exports);
+  static void Init(JavaState* state, Isolate* isolate_,
+                   Local<ObjectTemplate> exports);
 
-  static void SetContext(Local<Context> context_);
+  static void SetContext(JavaState* state, Local<Context> context_);
 
-  static void CallbackRegister(Isolate* isolate_, Local<Context> context);
-
-  static Persistent<Function> resolverUITask_;
-  static Persistent<Context> resolverContext_;
+  static void CallbackRegister(JavaState* state, Isolate* isolate_,
+                               Local<Context> context);
 
 private:
   explicit JavaWrapper(std::string package) : package_(package) {};
@@ -47,9 +63,6 @@ private:
 
   jlong ptr_;
   std::string package_;
-
-  static Persistent<FunctionTemplate> constructor_;
-  static Persistent<Function> registerUITask_;
 };
 
 #endif  // JNI_WRAPPER_H_
\ No newline at end of file
@@ -1,37 +1,283 @@
 extern crate utf8_util;
 
-use libc;
+use std::sync::RwLock;
 
-#[derive(Clone, Copy)]
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+#[doc(hidden)]
+pub use log;
+
+/// Priorities of `android.util.Log`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(isize)]
 pub enum LogPriority {
+    VERBOSE = 2,
     DEBUG = 3,
+    INFO = 4,
+    WARN = 5,
     ERROR = 6,
+    FATAL = 7,
+}
+
+impl LogPriority {
+    pub fn from_i32(priority: i32) -> Option<Self> {
+        match priority {
+            2 => Some(LogPriority::VERBOSE),
+            3 => Some(LogPriority::DEBUG),
+            4 => Some(LogPriority::INFO),
+            5 => Some(LogPriority::WARN),
+            6 => Some(LogPriority::ERROR),
+            7 => Some(LogPriority::FATAL),
+            _ => None,
+        }
+    }
+
+    /// The letter logcat prints for the priority.
+    pub fn letter(self) -> char {
+        match self {
+            LogPriority::VERBOSE => 'V',
+            LogPriority::DEBUG => 'D',
+            LogPriority::INFO => 'I',
+            LogPriority::WARN => 'W',
+            LogPriority::ERROR => 'E',
+            LogPriority::FATAL => 'F',
+        }
+    }
+}
+
+impl From<Level> for LogPriority {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Error => LogPriority::ERROR,
+            Level::Warn => LogPriority::WARN,
+            Level::Info => LogPriority::INFO,
+            Level::Debug => LogPriority::DEBUG,
+            Level::Trace => LogPriority::VERBOSE,
+        }
+    }
+}
+
+/// Longest message written as one logcat entry, liblog truncates longer ones.
+/// Its limit is 4068 bytes for the tag and the message together.
+const LOG_LINE_MAX: usize = 4000;
+
+/// Longest tag written, longer ones are cut.
+#[cfg(any(target_os = "android", test))]
+const LOG_TAG_MAX: usize = 64;
+
+/// Splits `msg` in chunks of at most `max` bytes, preferably after a newline.
+fn split_message(mut msg: &str, max: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    while msg.len() > max {
+        let mut end = max;
+        while !msg.is_char_boundary(end) {
+            end -= 1;
+        }
+        if let Some(newline) = msg[..end].rfind('\n') {
+            end = newline + 1;
+        }
+        let (chunk, rest) = msg.split_at(end);
+        chunks.push(chunk);
+        msg = rest;
+    }
+    chunks.push(msg);
+    chunks
 }
 
+/// Copies `s` into `buf` as a C string, interior NULs become `?`. It is cut at a char
+/// boundary if it doesn't fit.
+#[cfg(any(target_os = "android", test))]
+fn to_c_buf(s: &str, buf: &mut [u8]) -> *const libc::c_char {
+    let mut len = s.len().min(buf.len() - 1);
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    for (dst, src) in buf.iter_mut().zip(s.as_bytes()[..len].iter()) {
+        *dst = if *src == 0 { b'?' } else { *src };
+    }
+    buf[len] = 0;
+    buf.as_ptr() as *const libc::c_char
+}
+
+#[cfg(target_os = "android")]
 extern "C" {
-    pub fn __android_log_print(
+    pub fn __android_log_write(
         prio: libc::c_int,
         tag: *const libc::c_char,
-        fmt: *const libc::c_char,
-        ...
+        text: *const libc::c_char,
     ) -> libc::c_int;
 }
 
+/// Writes `msg` to logcat, long messages are split over several entries.
+#[cfg(target_os = "android")]
+pub fn log_write(prio: LogPriority, tag: &str, msg: &str) {
+    // Stack buffers instead of two CStrings per call
+    let mut tag_buf = [0u8; LOG_TAG_MAX + 1];
+    let mut line_buf = [0u8; LOG_LINE_MAX + 1];
+    let tag = to_c_buf(tag, &mut tag_buf);
+    for chunk in split_message(msg, LOG_LINE_MAX) {
+        let line = to_c_buf(chunk, &mut line_buf);
+        unsafe { __android_log_write(prio as libc::c_int, tag, line) };
+    }
+}
+
+/// Host builds have no logcat, write to stderr instead. Messages are split like on
+/// Android.
+#[cfg(not(target_os = "android"))]
+pub fn log_write(prio: LogPriority, tag: &str, msg: &str) {
+    for chunk in split_message(msg, LOG_LINE_MAX) {
+        eprintln!("{}/{}: {}", prio.letter(), tag, chunk);
+    }
+}
+
+/// A `log` backend writing to logcat, or to stderr on host builds.
+///
+/// A record is tagged with the tag of the longest module prefix registered with
+/// `module_tag`, or with the default tag.
+pub struct NdkLogger {
+    tag: String,
+    module_tags: RwLock<Vec<(String, String)>>,
+}
+
+impl NdkLogger {
+    pub fn new<T: Into<String>>(tag: T) -> Self {
+        NdkLogger {
+            tag: tag.into(),
+            module_tags: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Tags the records of `module` and its submodules with `tag`.
+    pub fn module_tag<M: Into<String>, T: Into<String>>(self, module: M, tag: T) -> Self {
+        self.set_module_tag(module, tag);
+        self
+    }
+
+    pub fn set_module_tag<M: Into<String>, T: Into<String>>(&self, module: M, tag: T) {
+        let module = module.into();
+        let mut tags = self.module_tags.write().unwrap();
+        tags.retain(|(prefix, _)| *prefix != module);
+        tags.push((module, tag.into()));
+    }
+
+    /// Installs the logger. Records more verbose than `level` are dropped, see
+    /// `set_max_level`.
+    pub fn init(self, level: LevelFilter) -> Result<&'static NdkLogger, SetLoggerError> {
+        let logger: &'static NdkLogger = Box::leak(Box::new(self));
+        log::set_logger(logger)?;
+        log::set_max_level(level);
+        Ok(logger)
+    }
+
+    fn write(&self, module: &str, prio: LogPriority, msg: &str) {
+        let tags = self.module_tags.read().unwrap();
+        log_write(prio, module_tag(&tags, module).unwrap_or(&self.tag), msg);
+    }
+}
+
+/// The tag of the longest prefix of `module`, a prefix matches whole path segments.
+fn module_tag<'a>(tags: &'a [(String, String)], module: &str) -> Option<&'a str> {
+    tags.iter()
+        .filter(|(prefix, _)| {
+            module.starts_with(prefix.as_str())
+                && (module.len() == prefix.len() || module[prefix.len()..].starts_with("::"))
+        })
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, tag)| tag.as_str())
+}
+
+impl Log for NdkLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let module = record.module_path().unwrap_or("");
+        self.write(module, record.level().into(), &record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+/// Changes the most verbose level logged, at any time.
+pub fn set_max_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+/// Logs a displayable message at debug level, through the installed `log` backend.
 #[macro_export]
 macro_rules! adb_debug {
     ($msg:expr) => {{
-        use std::ffi::CString;
-        unsafe {
-            $crate::__android_log_print(
-                $crate::LogPriority::DEBUG as libc::c_int,
-                CString::new("Rust Runtime")
-                    .expect("CString::new failed")
-                    .as_ptr(),
-                CString::new(format!("{:?}", $msg))
-                    .expect("CString::new failed")
-                    .as_ptr(),
-            );
-        };
+        $crate::log::debug!("{}", $msg);
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+
+    #[test]
+    fn split_message_keeps_chars_whole() {
+        // 'é' is two bytes, the limit falls in the middle of the second one
+        let chunks = split_message("aéé", 4);
+        assert_eq!(chunks, vec!["aé", "é"]);
+        assert_eq!(split_message("", 4), vec![""]);
+    }
+
+    #[test]
+    fn split_message_prefers_newlines() {
+        let chunks = split_message("ab\ncdefgh", 8);
+        assert_eq!(chunks, vec!["ab\n", "cdefgh"]);
+        // Without a newline the chunk is cut at the limit
+        assert_eq!(split_message("abcdef", 4), vec!["abcd", "ef"]);
+    }
+
+    #[test]
+    fn longest_module_prefix_wins() {
+        let logger = NdkLogger::new("app")
+            .module_tag("foo", "Foo")
+            .module_tag("foo::bar", "FooBar");
+        let tags = logger.module_tags.read().unwrap();
+
+        assert_eq!(module_tag(&tags, "foo"), Some("Foo"));
+        assert_eq!(module_tag(&tags, "foo::baz"), Some("Foo"));
+        assert_eq!(module_tag(&tags, "foo::bar::baz"), Some("FooBar"));
+        assert_eq!(module_tag(&tags, "foobar"), None);
+        assert_eq!(module_tag(&tags, "bar::foo"), None);
+    }
+
+    #[test]
+    fn set_module_tag_replaces_the_tag() {
+        let logger = NdkLogger::new("app").module_tag("foo", "Foo");
+        logger.set_module_tag("foo", "Other");
+        let tags = logger.module_tags.read().unwrap();
+
+        assert_eq!(tags.len(), 1);
+        assert_eq!(module_tag(&tags, "foo"), Some("Other"));
+    }
+
+    #[test]
+    fn c_buf_replaces_interior_nuls() {
+        let mut buf = [0xffu8; 8];
+        let s = unsafe { CStr::from_ptr(to_c_buf("a\0b", &mut buf)) };
+        assert_eq!(s.to_bytes(), b"a?b");
+    }
+
+    #[test]
+    fn c_buf_truncates_long_tags() {
+        let mut buf = [0u8; LOG_TAG_MAX + 1];
+        let tag = "t".repeat(LOG_TAG_MAX + 10);
+        let s = unsafe { CStr::from_ptr(to_c_buf(&tag, &mut buf)) };
+        assert_eq!(s.to_bytes(), &tag.as_bytes()[..LOG_TAG_MAX]);
+
+        // Cut before a char which doesn't fit
+        let tag = format!("{}é", "t".repeat(LOG_TAG_MAX - 1));
+        let s = unsafe { CStr::from_ptr(to_c_buf(&tag, &mut buf)) };
+        assert_eq!(s.to_str().unwrap(), "t".repeat(LOG_TAG_MAX - 1));
+    }
+}
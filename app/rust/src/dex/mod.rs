@@ -1,138 +1,92 @@
-use std::borrow::Cow;
 use std::collections::HashMap;
-use std::string::ToString;
-use std::sync::Mutex;
 
-use jni::errors::Result;
-use jni::objects::{AutoLocal, GlobalRef, JClass, JObject, JString, JValue};
-use jni::signature::TypeSignature;
-use jni::strings::{JNIString, JavaStr};
-use jni::JNIEnv;
+#[cfg(feature = "android")]
+mod java;
+#[cfg(feature = "android")]
+pub use self::java::*;
 
-use crate::ndk_util::jni_string_to_string;
-
-extern "C" {
-    fn throw_exception(data: *const u8, len: u32);
-}
-
-lazy_static! {
-    static ref CLASS_TABLE: Mutex<HashMap<String, GlobalRef>> = Mutex::new(HashMap::new());
+/// Java classes by name. Native threads can't find the app's classes with `FindClass`,
+/// so the app registers the ones native code calls into.
+pub struct ClassTable<R> {
+    classes: HashMap<String, R>,
 }
 
-pub fn print_exception(env: &JNIEnv) {
-    let exception_occurred = env.exception_check().unwrap_or_else(|e| panic!("{:?}", e));
-
-    if exception_occurred {
-        env.exception_describe()
-            .unwrap_or_else(|e| panic!("{:?}", e));
+impl<R> ClassTable<R> {
+    pub fn new() -> Self {
+        ClassTable {
+            classes: HashMap::new(),
+        }
     }
-}
-
-#[allow(dead_code)]
-pub fn throw_js_exception(env: &JNIEnv, message: JValue) -> Result<()> {
-    let message = JavaStr::from_env(env, JString::from(message.l()?))?;
-    let message: Cow<str> = (&message).into();
 
-    unsafe {
-        throw_exception(message.as_ptr(), message.len() as u32);
+    /// Registers `name` with the reference made by `create`, which is only called the
+    /// first time. Returns whether the class was added.
+    pub fn register<E, F>(&mut self, name: &str, create: F) -> Result<bool, E>
+    where
+        F: FnOnce() -> Result<R, E>,
+    {
+        if self.classes.contains_key(name) {
+            return Ok(false);
+        }
+        self.classes.insert(name.to_owned(), create()?);
+        Ok(true)
     }
 
-    Ok(())
-}
-
-#[allow(dead_code)]
-pub fn unwrap<T>(env: &JNIEnv, res: Result<T>) -> T {
-    res.unwrap_or_else(|e| {
-        print_exception(&env);
-        panic!(e)
-    })
-}
-
-#[allow(dead_code)]
-pub fn unwrap_js<T>(env: &JNIEnv, res: Result<T>) -> Option<T> {
-    match res {
-        Ok(result) => Some(result),
-        Err(err) => {
-            adb_debug!(err);
-            None
-        }
+    pub fn get(&self, name: &str) -> Result<&R, String> {
+        self.classes
+            .get(name)
+            .ok_or_else(|| format!("Class {} is not registered", name))
     }
 }
 
-#[no_mangle]
-#[allow(non_snake_case)]
-pub extern "C" fn Java_com_node_util_Util_createReference(
-    env: JNIEnv,
-    _class: JClass,
-    class_name: JString,
-) {
-    let class_name = jni_string_to_string(&env, class_name);
-    let mut table = CLASS_TABLE.lock().unwrap();
-
-    if table.contains_key(&class_name) {
-        adb_debug!(format!("Class {} already registered!", &class_name));
-        return;
+impl<R> Default for ClassTable<R> {
+    fn default() -> Self {
+        Self::new()
     }
-
-    let atomic_ref = {
-        let class = env.find_class(&class_name).unwrap();
-        let local_ref = AutoLocal::new(
-            &env,
-            unwrap(
-                &env,
-                env.new_object(
-                    "java/util/concurrent/atomic/AtomicReference",
-                    "(Ljava/lang/Object;)V",
-                    &[JValue::from(*class)],
-                ),
-            ),
-        );
-        unwrap(&env, env.new_global_ref(local_ref.as_obj()))
-    };
-
-    table.insert(class_name, atomic_ref);
 }
 
-pub fn call_static_method<'a, U, V>(
-    env: &'a JNIEnv,
-    class: &'a str,
-    name: U,
-    sig: V,
-    args: &[JValue],
-) -> Result<JValue<'a>>
-where
-    U: Into<JNIString>,
-    V: Into<JNIString> + AsRef<str>,
-{
-    let table = CLASS_TABLE.lock().unwrap();
-    let parsed = TypeSignature::from_str(&sig)?;
-    let class_name = class.to_string();
-
-    let class_ref = table.get(&class_name).unwrap();
-    let value = env.call_method(class_ref.as_obj(), "get", "()Ljava/lang/Object;", &[])?;
+#[cfg(test)]
+mod tests {
+    use super::ClassTable;
 
-    let class = JClass::from(value.l()?);
-    env.call_static_method_unchecked(class, (class, name, sig), parsed.ret, args)
-}
+    #[test]
+    fn classes_are_registered_once() {
+        let mut table = ClassTable::new();
+        assert_eq!(
+            table.register::<(), _>("com/node/util/Util", || Ok(1)),
+            Ok(true)
+        );
+        assert_eq!(
+            table.register::<(), _>("com/node/util/Util", || panic!("created twice")),
+            Ok(false)
+        );
+        assert_eq!(
+            table.register::<(), _>("com/node/util/JNIHelper", || Ok(2)),
+            Ok(true)
+        );
 
-pub fn call_method<'a, U, V>(
-    env: &'a JNIEnv,
-    instance: JObject<'a>,
-    name: U,
-    sig: V,
-    args: &[JValue],
-) -> Result<JValue<'a>>
-where
-    U: Into<JNIString>,
-    V: Into<JNIString> + AsRef<str>,
-{
-    let parsed = TypeSignature::from_str(&sig)?;
+        assert_eq!(table.get("com/node/util/Util"), Ok(&1));
+        assert_eq!(table.get("com/node/util/JNIHelper"), Ok(&2));
+    }
 
-    let class = unwrap(
-        &env,
-        env.call_method(instance, "getClass", "()Ljava/lang/Class;", &[]),
-    );
+    #[test]
+    fn failed_registrations_are_not_kept() {
+        let mut table = ClassTable::<u32>::new();
+        assert_eq!(
+            table.register("com/node/Missing", || Err("not found")),
+            Err("not found")
+        );
+        assert_eq!(
+            table.register::<&str, _>("com/node/Missing", || Ok(1)),
+            Ok(true)
+        );
+    }
 
-    let class = env.auto_local(unwrap(&env, class.l()));
-    env.call_method_unchecked(instance, (&class, name, sig), parsed.ret, args)
+    #[test]
+    fn unregistered_classes_are_named_in_the_error() {
+        let table = ClassTable::<u32>::new();
+        assert_eq!(
+            table.get("com/node/util/Util"),
+            Err("Class com/node/util/Util is not registered".to_owned())
+        );
+    }
 }
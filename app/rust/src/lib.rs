@@ -3,10 +3,12 @@ extern crate core;
 extern crate futures;
 #[macro_use]
 extern crate itertools;
+#[cfg(feature = "android")]
 extern crate jni;
 #[macro_use]
 extern crate lazy_static;
 extern crate libc;
+extern crate log;
 #[macro_use]
 extern crate ndk_log;
 extern crate reqwest;
@@ -21,26 +23,52 @@ extern crate utf8_util;
 extern crate v8;
 extern crate v8_macros;
 
-use jni::objects::JClass;
+#[cfg(feature = "android")]
+use std::sync::Arc;
+
+#[cfg(feature = "android")]
+use jni::objects::{GlobalRef, JClass, JObject, JString, JValue};
+#[cfg(feature = "android")]
+use jni::sys::{jint, jlong};
+#[cfg(feature = "android")]
 use jni::JNIEnv;
-use libc::size_t;
+#[cfg(feature = "android")]
+use log::LevelFilter;
+#[cfg(feature = "android")]
+use ndk_log::{LogPriority, NdkLogger};
 use v8::fun::CallbackInfo;
 use v8::types::*;
 use v8_macros::v8_fn;
 
-use crate::runtime::event_loop::init_event_loop;
+#[cfg(feature = "android")]
+use crate::ndk_util::jni_string_to_string;
+#[cfg(feature = "android")]
+use crate::runtime::config::{RejectionPolicy, RuntimeConfig};
+#[cfg(feature = "android")]
+use crate::runtime::error::JsError;
+#[cfg(feature = "android")]
+use crate::runtime::event_loop::WorkerThread;
+#[cfg(feature = "android")]
+use crate::runtime::modules::AssetModuleSource;
+#[cfg(feature = "android")]
+use crate::v8_jni::jvm;
 
 #[macro_use]
 mod macros;
+#[cfg(feature = "android")]
 #[macro_use]
 mod ndk_graphics;
-#[macro_use]
+// Only the JNI bridge registers classes, the table is built on host for its tests
+#[cfg_attr(not(feature = "android"), allow(dead_code))]
 mod dex;
 #[macro_use]
-mod runtime;
+pub mod runtime;
+#[cfg(feature = "android")]
 mod ndk_util;
+#[cfg(feature = "android")]
 mod v8_jni;
 
+#[cfg(feature = "android")]
 #[no_mangle]
 pub unsafe extern "C" fn get_android_version(env: &JNIEnv) -> i32 {
     // Android Version
@@ -50,21 +78,134 @@ pub unsafe extern "C" fn get_android_version(env: &JNIEnv) -> i32 {
         .unwrap() as i32
 }
 
+/// Starts a worker on its own thread, the returned pointer is owned by the Java `Worker`.
+#[cfg(feature = "android")]
 #[no_mangle]
 #[allow(non_snake_case)]
-pub extern "C" fn Java_com_node_sample_MainActivity_demoMain(env: JNIEnv, _class: JClass) {
-    init_event_loop();
+pub extern "C" fn Java_com_node_util_v8_Worker_createWorker(
+    env: JNIEnv,
+    _class: JClass,
+    assets: JObject,
+    listener: JObject,
+) -> jlong {
+    // Fails once a logger is installed, by an earlier worker
+    let _ = NdkLogger::new("Rust Runtime").init(LevelFilter::Debug);
+
+    let mut config = RuntimeConfig::default();
+    match AssetModuleSource::new(&env, assets) {
+        Ok(modules) => config.modules = Arc::new(modules),
+        Err(err) => adb_debug!(err),
+    }
+    config.rejection_policy = if listener.is_null() {
+        RejectionPolicy::Log
+    } else {
+        match env.new_global_ref(listener) {
+            Ok(listener) => RejectionPolicy::Callback(Arc::new(move |err: &JsError| {
+                forward_rejection(&listener, err)
+            })),
+            Err(err) => {
+                adb_debug!(err);
+                RejectionPolicy::Log
+            }
+        }
+    };
+    Box::into_raw(Box::new(WorkerThread::spawn(config))) as jlong
 }
 
-type Buf = *mut u8;
+/// Calls `Worker.RejectionListener#onUnhandledRejection` from the worker thread.
+#[cfg(feature = "android")]
+fn forward_rejection(listener: &GlobalRef, err: &JsError) {
+    let result = jvm().attach_current_thread_as_daemon().and_then(|env| {
+        let message = env.new_string(err.to_string())?;
+        env.call_method(
+            listener.as_obj(),
+            "onUnhandledRejection",
+            "(Ljava/lang/String;)V",
+            &[JValue::from(JObject::from(message))],
+        )
+        .map(|_| ())
+    });
+    if let Err(e) = result {
+        adb_debug!(format!("Failed to forward rejection: {}", e));
+    }
+}
+
+#[cfg(feature = "android")]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_com_node_util_v8_Worker_eval(
+    env: JNIEnv,
+    _class: JClass,
+    ptr: jlong,
+    name: JString,
+    script: JString,
+) {
+    let worker = &*(ptr as *const WorkerThread);
+    worker.eval(
+        jni_string_to_string(&env, name),
+        jni_string_to_string(&env, script),
+    );
+}
+
+#[cfg(feature = "android")]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_com_node_util_v8_Worker_evalModule(
+    env: JNIEnv,
+    _class: JClass,
+    ptr: jlong,
+    specifier: JString,
+) {
+    let worker = &*(ptr as *const WorkerThread);
+    worker.eval_module(jni_string_to_string(&env, specifier));
+}
 
+/// Sets the most verbose `android.util.Log` priority logged by the Rust runtime.
+#[cfg(feature = "android")]
 #[no_mangle]
-pub unsafe extern "C" fn worker_send_bytes(
-    _buf: Buf,
-    _len: size_t,
-    _callback: Handle<JsFunction>,
-) -> *const libc::c_char {
+#[allow(non_snake_case)]
+pub extern "C" fn Java_com_node_util_v8_Worker_setLogLevel(
+    _env: JNIEnv,
+    _class: JClass,
+    priority: jint,
+) {
+    let level = match LogPriority::from_i32(priority) {
+        Some(LogPriority::VERBOSE) => LevelFilter::Trace,
+        Some(LogPriority::DEBUG) => LevelFilter::Debug,
+        Some(LogPriority::INFO) => LevelFilter::Info,
+        Some(LogPriority::WARN) => LevelFilter::Warn,
+        Some(LogPriority::ERROR) | Some(LogPriority::FATAL) => LevelFilter::Error,
+        None => LevelFilter::Off,
+    };
+    ndk_log::set_max_level(level);
+}
+
+/// Terminates the worker and frees it, `ptr` must not be used afterwards.
+#[cfg(feature = "android")]
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "C" fn Java_com_node_util_v8_Worker_terminate(
+    _env: JNIEnv,
+    _class: JClass,
+    ptr: jlong,
+) {
+    let mut worker = Box::from_raw(ptr as *mut WorkerThread);
+    worker.terminate();
+}
+
+#[v8_fn]
+pub fn worker_send_bytes(args: &CallbackInfo) {
+    let (buffer, callback) = match (args.get::<JsArrayBuffer>(0), args.get::<JsFunction>(1)) {
+        (Ok(buffer), Ok(callback)) => (buffer, callback),
+        _ => return args.throw_type_error("$sendBuffer(buffer, callback)"),
+    };
+    adb_debug!(format!("Received {} bytes", buffer.len()));
+
+    let scope = &args.scope();
+    args.set_return_value(v8::new_string(scope, "💖"));
+
     let info = js_object!(
+        scope,
         "name" => "Vampire",
         "gender" => "Male",
         "age" => 28,
@@ -75,32 +216,40 @@ pub unsafe extern "C" fn worker_send_bytes(
         ]
     );
 
-    let result = _callback.call::<JsNull, JsObject, _, _>(v8::null(), vec![info]);
-    adb_debug!(result);
+    let result = match callback.call::<JsNull, JsObject, _, _>(scope, v8::null(scope), vec![info]) {
+        Ok(result) => result,
+        Err(err) => {
+            adb_debug!(format!("Callback failed: {}", err));
+            return;
+        }
+    };
+    adb_debug!(format!("{:?}", result));
 
     // String playground
-    let get_name: Handle<JsFunction> = result.get("getName");
-    let name: Handle<JsString> = get_name.call(result, v8::empty_args());
-    adb_debug!(format!("Name: {:?}", name));
+    if let Ok(get_name) = result.get::<JsFunction, _>(scope, "getName") {
+        match get_name.call::<_, JsString, _, _>(scope, result, v8::empty_args()) {
+            Ok(name) => adb_debug!(format!("Name: {:?}", name)),
+            Err(err) => adb_debug!(format!("getName failed: {}", err)),
+        }
+    }
 
     // Promise playground
-    let get_promise: Handle<JsFunction> = result.get("getPromise");
-    let promise: Handle<JsPromise> = get_promise.call(result, v8::empty_args());
-
-    promise.then(JsFunction::new(promise_resolver));
-
-    c_str!("💖")
+    if let Ok(get_promise) = result.get::<JsFunction, _>(scope, "getPromise") {
+        match get_promise.call::<_, JsPromise, _, _>(scope, result, v8::empty_args()) {
+            Ok(promise) => promise.then(JsFunction::new(scope, promise_resolver)),
+            Err(err) => adb_debug!(format!("getPromise failed: {}", err)),
+        }
+    }
 }
 
 #[v8_fn]
-pub fn promise_resolver(args: &CallbackInfo) {
-    let username: Handle<JsString> = args.get(0);
-    adb_debug!(format!("Username: {:?}", username));
+pub fn promise_resolver(username: String) {
+    adb_debug!(format!("Username: {}", username));
 }
 
 #[v8_fn]
 pub fn test_fn(args: &CallbackInfo) {
-    args.set_return_value(v8::new_array_buffer(&"💖".as_bytes()));
+    args.set_return_value(v8::new_array_buffer(&args.scope(), &"💖".as_bytes()));
 }
 
 #[allow(dead_code)]
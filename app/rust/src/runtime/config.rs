@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use crate::runtime::console;
+use crate::runtime::error::JsError;
+use crate::runtime::http::{HttpTransport, ReqwestTransport};
+use crate::runtime::modules::{FsModuleSource, ModuleSource};
+
+/// Settings of a `Worker` and its isolate.
+#[derive(Clone)]
+pub struct RuntimeConfig {
+    /// Transport behind `fetch`, see `HttpConfig::build`.
+    pub http: Arc<dyn HttpTransport>,
+    /// Root of `import` and `require`, the working directory by default.
+    pub modules: Arc<dyn ModuleSource>,
+    pub rejection_policy: RejectionPolicy,
+    /// Log tag of the JS console.
+    pub console_tag: String,
+}
+
+/// What happens to a promise rejected without a handler, once no JS
+/// `unhandledrejection` listener called `preventDefault()`.
+#[derive(Clone)]
+pub enum RejectionPolicy {
+    /// Fail `execute` or the event loop with the rejection.
+    Error,
+    /// Log the rejection as an error and keep running.
+    Log,
+    /// Terminate the worker, the rejection is returned like with `Error`.
+    Terminate,
+    /// Hand the rejection over, e.g. to a Java listener, and keep running.
+    Callback(Arc<dyn Fn(&JsError) + Send + Sync>),
+}
+
+impl Default for RejectionPolicy {
+    fn default() -> Self {
+        RejectionPolicy::Error
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            http: Arc::new(ReqwestTransport::default()),
+            modules: Arc::new(FsModuleSource::new(".")),
+            rejection_policy: RejectionPolicy::default(),
+            console_tag: console::DEFAULT_TAG.to_owned(),
+        }
+    }
+}
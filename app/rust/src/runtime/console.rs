@@ -0,0 +1,22 @@
+use std::ffi::CStr;
+
+use libc::{c_char, c_int, c_void};
+use ndk_log::{log_write, LogPriority};
+
+use crate::runtime::isolate::Isolate;
+
+pub const DEFAULT_TAG: &str = "JS Console";
+
+/// Backs `$print`, which the JS console writes through.
+#[no_mangle]
+pub extern "C" fn console_print(data: *mut c_void, priority: c_int, message: *const c_char) {
+    let priority = LogPriority::from_i32(priority).unwrap_or(LogPriority::INFO);
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    // Scripts only run once the isolate pointer is set, but stay on the safe side
+    let tag = if data.is_null() {
+        DEFAULT_TAG
+    } else {
+        unsafe { Isolate::from_raw_ptr(data) }.console_tag()
+    };
+    log_write(priority, tag, &message);
+}
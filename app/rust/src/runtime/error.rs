@@ -0,0 +1,167 @@
+use std::error::Error;
+use std::fmt;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::runtime::modules::ModuleError;
+use crate::runtime::DenoC;
+
+extern "C" {
+    fn reject(d: *const DenoC, promise_id: u32, error: *const libc::c_char);
+}
+
+/// An op failure, surfaced to JS as an `Error` with the same name, message and code.
+#[derive(Debug, Serialize)]
+pub struct OpError {
+    pub name: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+impl OpError {
+    pub fn new<N: Into<String>, M: Into<String>>(name: N, message: M) -> Self {
+        OpError {
+            name: name.into(),
+            message: message.into(),
+            code: None,
+        }
+    }
+
+    pub fn error<M: Into<String>>(message: M) -> Self {
+        OpError::new("Error", message)
+    }
+
+    pub fn type_error<M: Into<String>>(message: M) -> Self {
+        OpError::new("TypeError", message)
+    }
+
+    pub fn with_code<C: Into<String>>(mut self, code: C) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+}
+
+impl fmt::Display for OpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(ref code) => write!(f, "{} [{}]: {}", self.name, code, self.message),
+            None => write!(f, "{}: {}", self.name, self.message),
+        }
+    }
+}
+
+impl From<serde_json::Error> for OpError {
+    fn from(e: serde_json::Error) -> Self {
+        OpError::type_error(format!("Invalid op arguments: {}", e)).with_code("ERR_INVALID_ARGS")
+    }
+}
+
+impl From<reqwest::Error> for OpError {
+    fn from(e: reqwest::Error) -> Self {
+        let code = if e.is_timeout() {
+            "ERR_TIMEOUT"
+        } else if e.is_redirect() {
+            "ERR_TOO_MANY_REDIRECTS"
+        } else {
+            "ERR_NETWORK"
+        };
+        OpError::type_error(e.to_string()).with_code(code)
+    }
+}
+
+impl From<tokio_timer::Error> for OpError {
+    fn from(e: tokio_timer::Error) -> Self {
+        OpError::error(e.to_string()).with_code("ERR_TIMER")
+    }
+}
+
+/// Rejects the JS promise registered as `promise_id` with `err`.
+pub fn reject_promise(d: *const DenoC, promise_id: u32, err: &OpError) {
+    adb_debug!(format!("Op {} failed: {}", promise_id, err));
+    let json = serde_json::to_string(err).expect("OpError is always serializable");
+    unsafe { reject(d, promise_id, c_str!(json)) };
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StackFrame {
+    #[serde(default)]
+    pub function_name: String,
+    #[serde(default)]
+    pub script_name: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// An exception thrown by a script or a module, or the reason of an unhandled rejection.
+/// Lines and columns are 1-based.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsError {
+    pub message: String,
+    #[serde(default)]
+    pub script_name: Option<String>,
+    #[serde(default)]
+    pub line: Option<u32>,
+    #[serde(default)]
+    pub column: Option<u32>,
+    #[serde(default)]
+    pub frames: Vec<StackFrame>,
+}
+
+impl JsError {
+    pub fn new<M: Into<String>>(message: M) -> Self {
+        JsError {
+            message: message.into(),
+            script_name: None,
+            line: None,
+            column: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Decodes the exception JSON written by `EncodeException` in api.cpp.
+    pub(crate) fn from_json(json: &str) -> Self {
+        serde_json::from_str(json).unwrap_or_else(|_| JsError::new(json))
+    }
+}
+
+impl fmt::Display for JsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if self.frames.is_empty() {
+            if let (Some(name), Some(line), Some(column)) =
+                (&self.script_name, self.line, self.column)
+            {
+                write!(f, "\n    at {}:{}:{}", name, line, column)?;
+            }
+        }
+        for frame in &self.frames {
+            if frame.function_name.is_empty() {
+                write!(
+                    f,
+                    "\n    at {}:{}:{}",
+                    frame.script_name, frame.line, frame.column
+                )?;
+            } else {
+                write!(
+                    f,
+                    "\n    at {} ({}:{}:{})",
+                    frame.function_name, frame.script_name, frame.line, frame.column
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for JsError {}
+
+impl From<ModuleError> for JsError {
+    fn from(e: ModuleError) -> Self {
+        let mut err = JsError::new(e.to_string());
+        err.script_name = e.referrer;
+        err
+    }
+}
@@ -1,148 +1,269 @@
 use std::sync::{Arc, Mutex};
 use std::thread;
 
-use futures::{Async, Future};
+use futures::sync::mpsc;
+use futures::{future, Async, Future, Stream};
+use tokio::runtime::current_thread;
 
-use crate::runtime::isolate;
+use crate::runtime::config::RuntimeConfig;
+use crate::runtime::error::JsError;
+use crate::runtime::isolate::{Isolate, TerminateHandle};
 
+/// An isolate and its pending ops. Polling the worker drives the ops, it completes
+/// once the isolate is idle or terminated.
 #[derive(Clone)]
 pub struct Worker {
-    inner: Arc<Mutex<isolate::Isolate>>,
+    inner: Arc<Mutex<Isolate>>,
+    terminate: TerminateHandle,
 }
 
 impl Worker {
-    fn new() -> Self {
+    pub fn new(config: RuntimeConfig) -> Self {
+        let isolate = Isolate::new(config);
+        let terminate = isolate.terminate_handle();
         Self {
-            inner: Arc::new(Mutex::new(isolate::Isolate::new())),
+            inner: Arc::new(Mutex::new(isolate)),
+            terminate,
         }
     }
 
-    fn execute(&mut self, script: &str) {
+    /// Runs `source`, errors and stack traces refer to it as `name`.
+    pub fn execute(&mut self, name: &str, source: &str) -> Result<(), JsError> {
         let mut isolate = self.inner.lock().unwrap();
-        isolate.execute(script);
+        isolate.execute(name, source)
+    }
+
+    /// Runs the ES module `specifier`, resolved against the configured module root.
+    pub fn execute_module(&mut self, specifier: &str) -> Result<(), JsError> {
+        let mut isolate = self.inner.lock().unwrap();
+        isolate.execute_module(specifier)
+    }
+
+    /// Runs the event loop on the current thread until no timer or request is pending.
+    /// Stops at the first unhandled promise rejection.
+    pub fn run_until_idle(&mut self) -> Result<(), JsError> {
+        // Ops are polled on a fresh runtime, it also provides the timer and the reactor
+        current_thread::block_on_all(self.clone())
+    }
+
+    /// Aborts running JS and drops pending ops, the worker can't execute scripts anymore.
+    /// Safe to call from any thread.
+    pub fn terminate(&self) {
+        self.terminate.terminate();
+    }
+
+    pub fn is_terminated(&self) -> bool {
+        self.terminate.is_terminated()
     }
 }
 
 impl Future for Worker {
     type Item = ();
-    type Error = ();
+    type Error = JsError;
 
     fn poll(&mut self) -> Result<Async<Self::Item>, Self::Error> {
         let mut isolate = self.inner.lock().unwrap();
-        isolate.poll().map_err(|err| adb_debug!(err))
+        isolate.poll()
     }
 }
 
-#[no_mangle]
-pub extern "C" fn init_event_loop() {
-    thread::spawn(move || {
-        let main_future = futures::lazy(move || unsafe {
-            let mut worker = Worker::new();
-            worker.execute(
-                r#"
-                // to keep event loop alive
-                setInterval(() => { }, 500);
-                
-                const Random = java.import('java/util/Random');
-                const random = new Random(10000);
-                 
-                console.log(`nextInt: ${random.nextInt()}`);
-                console.log(`nextDouble: ${random.nextDouble()}`);
-                
-                const context = java.import('context');
-                const colorList = [
-                    '#1abc9c',
-                    '#2ecc71',
-                    '#3498db',
-                    '#9b59b6',
-                    '#34495e',
-                    '#16a085',
-                    '#27ae60',
-                    '#2980b9',
-                    '#8e44ad',
-                    '#2c3e50',
-                    '#f1c40f',
-                    '#e67e22',
-                    '#e74c3c',
-                    '#ecf0f1',
-                    '#95a5a6',
-                    '#f39c12',
-                    '#d35400',
-                    '#c0392b',
-                    '#bdc3c7',
-                    '#7f8c8d'
-                ];
-                
-                function changeColor(context) {
-                    setInterval(async () => {
-                        const color = colorList[Math.ceil(Math.random() * colorList.length)];
-                        await context.setTextColor(color);
-                    }, 2000);
-                }
-                
-                function format(value) {
-                    return value > 9 ? value: '0' + value;
-                }
-                
-                function createTimeString() {
-                    const date = new Date();
-                    const h = date.getHours();
-                    const m = date.getMinutes();
-                    const s = date.getSeconds();
-                    return `${format(h)}:${format(m)}:${format(s)}`;
+enum Job {
+    Script { name: String, source: String },
+    Module(String),
+}
+
+/// A worker living on its own thread, scripts are sent to it and run in order.
+/// It keeps running while idle until terminated.
+pub struct WorkerThread {
+    jobs: mpsc::UnboundedSender<Job>,
+    terminate: TerminateHandle,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl WorkerThread {
+    pub fn spawn(config: RuntimeConfig) -> Self {
+        let (jobs, mut receiver) = mpsc::unbounded::<Job>();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            // The isolate is bound to the thread which created it
+            let mut worker = Worker::new(config);
+            tx.send(worker.terminate.clone()).unwrap();
+
+            let main_future = future::poll_fn(move || -> Result<Async<()>, ()> {
+                while let Async::Ready(Some(job)) = receiver.poll()? {
+                    let result = match job {
+                        Job::Script { name, source } => worker.execute(&name, &source),
+                        Job::Module(specifier) => worker.execute_module(&specifier),
+                    };
+                    if let Err(err) = result {
+                        log::error!("Uncaught {}", err);
+                    }
                 }
-                
-                function clock(context) {
-                    setInterval(async () => {
-                        await context.setText(createTimeString());
-                    }, 500);
+                loop {
+                    match worker.poll() {
+                        Ok(Async::Ready(())) if worker.is_terminated() => {
+                            return Ok(Async::Ready(()))
+                        }
+                        Ok(_) => return Ok(Async::NotReady),
+                        // Keeps running, like a browser does
+                        Err(err) => log::error!("Uncaught (in promise) {}", err),
+                    }
                 }
-                
-                changeColor(context);
-                clock(context);
-                
-                // Send to Rust world by ArrayBuffer
-                const ab = new ArrayBuffer(10);
-                const bufView = new Uint8Array(ab);
-
-                $sendBuffer(ab, data => {
-                    return {
-                        ...data,
-                        getName() { return this.name; },
-                        getPromise() {
-                            /*return fetch('https://api.github.com/users/ardanlabs')
-                                .then(resp => resp.json())
-                                .then(user => user.name);*/
-                            return Promise.resolve('hoangpq');
-                        },
-                    };
-                });
+            });
+
+            let _ = current_thread::block_on_all(main_future);
+            adb_debug!("Worker thread exited");
+        });
 
-                const users = ['hoangpq', 'firebase'];
+        WorkerThread {
+            jobs,
+            terminate: rx.recv().expect("worker thread died during startup"),
+            thread: Some(thread),
+        }
+    }
+
+    /// Queues `source` to be executed on the worker thread, errors refer to it as `name`.
+    pub fn eval(&self, name: String, source: String) {
+        self.send(Job::Script { name, source });
+    }
 
-                function fetchUserInfo(user) {
-                    return fetch(`https://api.github.com/users/${user}`)
-                        .then(resp => resp.json());
+    /// Queues the ES module `specifier` to be executed on the worker thread.
+    pub fn eval_module(&self, specifier: String) {
+        self.send(Job::Module(specifier));
+    }
+
+    fn send(&self, job: Job) {
+        if self.jobs.unbounded_send(job).is_err() {
+            adb_debug!("Worker is terminated, script dropped");
+        }
+    }
+
+    /// Terminates the worker and waits for its thread to exit.
+    pub fn terminate(&mut self) {
+        self.terminate.terminate();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for WorkerThread {
+    fn drop(&mut self) {
+        self.terminate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::runtime::config::RejectionPolicy;
+    use crate::runtime::http::tests::TestTransport;
+
+    /// Fetches on every tick of an interval, the body and the tick are reported as an
+    /// unhandled rejection.
+    const TICKS: &str = "let ticks = 0;
+                         setInterval(() => {
+                             const tick = ++ticks;
+                             fetch('http://test/')
+                                 .then(res => res.text())
+                                 .then(body => Promise.reject(new Error(body + ' ' + tick)));
+                         }, 5);";
+
+    /// Fetches after three overlapping timeouts, the bodies and delays are reported as
+    /// one rejection once all of them resolved.
+    const OVERLAPPING: &str = "const fired = [];
+                               for (const delay of [30, 10, 20]) {
+                                   setTimeout(() => fetch('http://test/')
+                                       .then(res => res.text())
+                                       .then(body => {
+                                           fired.push(`${body} ${delay}`);
+                                           if (fired.length === 3) {
+                                               return Promise.reject(new Error(fired.sort().join()));
+                                           }
+                                       }), delay);
+                               }";
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A worker whose transport answers `body`, its rejections are sent to the receiver.
+    fn spawn(body: &'static str) -> (WorkerThread, Receiver<String>) {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let config = RuntimeConfig {
+            http: TestTransport::new(200, body),
+            rejection_policy: RejectionPolicy::Callback(Arc::new(move |err: &JsError| {
+                let _ = tx.lock().unwrap().send(err.message.clone());
+            })),
+            ..RuntimeConfig::default()
+        };
+        (WorkerThread::spawn(config), rx)
+    }
+
+    fn expect_ticks(rx: &Receiver<String>, body: &str, count: usize) {
+        for _ in 0..count {
+            let message = rx.recv_timeout(TIMEOUT).unwrap();
+            assert!(message.contains(body), "{}", message);
+        }
+    }
+
+    #[test]
+    fn worker_threads_terminate_independently() {
+        let (mut first, first_ticks) = spawn("first");
+        let (second, second_ticks) = spawn("second");
+        first.eval("ticks.js".to_owned(), TICKS.to_owned());
+        second.eval("ticks.js".to_owned(), TICKS.to_owned());
+
+        expect_ticks(&first_ticks, "first", 3);
+        expect_ticks(&second_ticks, "second", 3);
+
+        first.terminate();
+        // The isolate and its callback are dropped once the thread exited
+        loop {
+            match first_ticks.recv_timeout(TIMEOUT) {
+                Ok(_) => continue,
+                Err(err) => {
+                    assert_eq!(err, RecvTimeoutError::Disconnected);
+                    break;
                 }
+            }
+        }
 
-                const start = Date.now();
-                setTimeout(() => {
-                    console.log(`timeout 3s: ${Date.now() - start}`);
-                }, 3000);
-
-                Promise.all(users.map(fetchUserInfo))
-                    .then(data => {
-                        const names = data.map(user => user.name).join(', ');
-                        console.log(`Name: ${names}`);
-                        console.log(`api call: ${Date.now() - start}`);
-                    })
-                    .catch(e => console.log(e.message));
-            "#,
-            );
-
-            worker
-        });
+        expect_ticks(&second_ticks, "second", 3);
+    }
 
-        tokio::runtime::current_thread::run(main_future);
-    });
+    #[test]
+    fn worker_threads_resolve_their_own_promises() {
+        let (first, first_fired) = spawn("first");
+        let (second, second_fired) = spawn("second");
+        first.eval("timers.js".to_owned(), OVERLAPPING.to_owned());
+        second.eval("timers.js".to_owned(), OVERLAPPING.to_owned());
+
+        // Both workers use the same promise ids, each must only settle its own
+        let message = first_fired.recv_timeout(TIMEOUT).unwrap();
+        assert!(
+            message.contains("first 10,first 20,first 30"),
+            "{}",
+            message
+        );
+        let message = second_fired.recv_timeout(TIMEOUT).unwrap();
+        assert!(
+            message.contains("second 10,second 20,second 30"),
+            "{}",
+            message
+        );
+
+        let idle = Duration::from_millis(100);
+        assert_eq!(
+            first_fired.recv_timeout(idle),
+            Err(RecvTimeoutError::Timeout)
+        );
+        assert_eq!(
+            second_fired.recv_timeout(idle),
+            Err(RecvTimeoutError::Timeout)
+        );
+    }
 }
@@ -1,51 +1,344 @@
+use std::sync::Arc;
+
 use futures::stream::Stream;
-use futures::Future;
-use libc::{c_char, c_void};
-use reqwest::r#async::Response;
+use futures::{future, Future};
+use libc::{c_char, size_t};
+use reqwest::StatusCode;
+use serde_derive::{Deserialize, Serialize};
 
+use crate::runtime::error::OpError;
+use crate::runtime::http::{HttpRequest, HttpResponse, HttpTransport};
 use crate::runtime::isolate::Isolate;
-use crate::runtime::{DenoC, OpAsyncFuture};
+use crate::runtime::resources::{ResourceArgs, ResourceTable};
+use crate::runtime::stream_cancel::cancelable;
+use crate::runtime::{op_args, op_err, op_ok, Buf, DenoC, OpAsyncFuture};
 
 extern "C" {
     fn resolve(d: *const DenoC, promise_id: u32, data: *const c_char);
+    fn resolve_buffer(
+        d: *const DenoC,
+        promise_id: u32,
+        value: *const c_char,
+        data: *const u8,
+        len: size_t,
+    );
+}
+
+#[derive(Deserialize)]
+struct FetchArgs {
+    url: String,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+}
+
+/// Response metadata, the body is read from resource `rid` with `readBody`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FetchResponse {
+    status: u16,
+    status_text: String,
+    headers: Vec<(String, String)>,
+    url: String,
+    rid: u32,
+}
+
+#[derive(Serialize)]
+struct ReadResult {
+    done: bool,
+}
+
+impl FetchResponse {
+    fn new(status: u16, headers: Vec<(String, String)>, url: String, rid: u32) -> Self {
+        let status_text = StatusCode::from_u16(status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or("");
+        FetchResponse {
+            status,
+            status_text: status_text.to_owned(),
+            headers,
+            url,
+            rid,
+        }
+    }
 }
 
-lazy_static! {
-    pub static ref CLIENT: reqwest::r#async::Client = reqwest::r#async::Client::new();
+fn fetch_async(
+    http: Arc<dyn HttpTransport>,
+    resources: ResourceTable,
+    args: FetchArgs,
+    body: &[u8],
+) -> impl Future<Item = String, Error = OpError> {
+    let request = HttpRequest {
+        method: args.method.unwrap_or_else(|| "GET".to_owned()),
+        url: args.url,
+        headers: args.headers,
+        body: body.to_vec(),
+    };
+
+    adb_debug!(format!("Send -> {} {}", request.method, request.url));
+
+    http.send(request).map(move |res| {
+        let HttpResponse {
+            status,
+            headers,
+            url,
+            body,
+        } = res;
+        // Hand the body over to JS as a stream of chunks
+        let rid = resources.add(body);
+        let meta = FetchResponse::new(status, headers, url, rid);
+        serde_json::to_string(&meta).expect("FetchResponse is always serializable")
+    })
 }
 
-pub fn fetch_async(d: *const DenoC, url: &str, promise_id: u32) -> OpAsyncFuture {
-    let raw_transform = |res: Response| {
-        res.into_body()
-            .concat2()
-            .map(|body| String::from_utf8(body.to_vec()).ok())
+/// Sends a request and resolves with the response metadata once headers arrived.
+pub fn op_fetch(
+    isolate: &mut Isolate,
+    promise_id: u32,
+    control: &[u8],
+    zero_copy: &[u8],
+) -> OpAsyncFuture {
+    let args = match op_args::<FetchArgs>(control) {
+        Ok(args) => args,
+        Err(err) => return op_err(err),
+    };
+
+    let (task, trigger) = cancelable(fetch_async(
+        isolate.http.clone(),
+        isolate.resources.clone(),
+        args,
+        zero_copy,
+    ));
+    isolate.add_cancel_handle(promise_id, trigger);
+
+    let d = unsafe { isolate.deno.as_ref() };
+    Box::new(task.map(move |meta| {
+        // None when aborted, JS already rejected the promise
+        if let Some(meta) = meta {
+            unsafe { resolve(d.unwrap(), promise_id, c_str!(meta)) };
+        }
+        Buf::default()
+    }))
+}
+
+/// Resolves with the next chunk of a response body, or `done` at its end.
+pub fn op_read_body(
+    isolate: &mut Isolate,
+    promise_id: u32,
+    control: &[u8],
+    _zero_copy: &[u8],
+) -> OpAsyncFuture {
+    let rid = match op_args::<ResourceArgs>(control) {
+        Ok(args) => args.rid,
+        Err(err) => return op_err(err),
+    };
+    let body = match isolate.resources.get(rid) {
+        Ok(body) => body,
+        Err(err) => return op_err(err),
     };
 
-    adb_debug!(format!("Send -> {}", url));
-    let d = unsafe { d.as_ref() };
-
-    Box::new(
-        CLIENT
-            .get(url)
-            .send()
-            .and_then(raw_transform)
-            .and_then(move |body| match body {
-                Some(body) => unsafe {
-                    let d = d.unwrap();
-                    resolve(d, promise_id, c_str!(body.clone()));
-                    Ok(boxed!(body.clone()))
-                },
-                None => Ok(boxed!("{}")),
-            })
-            .map_err(|e| adb_debug!(e)),
-    )
-}
-
-#[no_mangle]
-fn fetch(isolate_ptr: *const c_void, url: *mut c_char, promise_id: u32) {
-    let url = unsafe { rust_str!(url) };
-    let isolate = unsafe { Isolate::from_raw_ptr(isolate_ptr) };
-    isolate
-        .pending_ops
-        .push(fetch_async(isolate.deno, url, promise_id));
+    let resources = isolate.resources.clone();
+    let read = future::poll_fn(move || body.lock().unwrap().poll()).then(move |read| {
+        // A drained or failed body has nothing more to give
+        match read {
+            Ok(Some(_)) => {}
+            _ => {
+                resources.close(rid);
+            }
+        }
+        read
+    });
+    let (task, trigger) = cancelable(read);
+    isolate.add_cancel_handle(promise_id, trigger);
+
+    let d = unsafe { isolate.deno.as_ref() };
+    Box::new(task.map(move |read| {
+        if let Some(chunk) = read {
+            let result = ReadResult {
+                done: chunk.is_none(),
+            };
+            let result = serde_json::to_string(&result).expect("ReadResult is always serializable");
+            let chunk = chunk.unwrap_or_default();
+            unsafe {
+                resolve_buffer(
+                    d.unwrap(),
+                    promise_id,
+                    c_str!(result),
+                    chunk.as_ptr(),
+                    chunk.len(),
+                );
+            }
+        }
+        Buf::default()
+    }))
+}
+
+/// Cancels the in-flight `fetch` or `readBody` op dispatched for `promise_id`.
+pub fn op_abort(
+    isolate: &mut Isolate,
+    promise_id: u32,
+    _control: &[u8],
+    _zero_copy: &[u8],
+) -> OpAsyncFuture {
+    isolate.cancel_op(promise_id);
+    op_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::thread;
+
+    use futures::{Future, Stream};
+    use tokio::runtime::current_thread;
+
+    use crate::runtime::config::RuntimeConfig;
+    use crate::runtime::error::OpError;
+    use crate::runtime::event_loop::Worker;
+    use crate::runtime::http::{HttpRequest, HttpTransport, ReqwestTransport};
+
+    /// Answers each of the next `count` connections with `response`.
+    fn serve(count: usize, response: &'static str) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                // Read the request head, the tests don't send a body
+                let mut head = Vec::new();
+                let mut byte = [0u8];
+                while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                    head.push(byte[0]);
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        addr
+    }
+
+    const HELLO: &str = "HTTP/1.1 201 Created\r\n\
+                         Content-Type: text/plain\r\n\
+                         X-Test: yes\r\n\
+                         Content-Length: 5\r\n\
+                         Connection: close\r\n\
+                         \r\n\
+                         hello";
+
+    fn get(url: String) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_owned(),
+            url,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn transport_reads_status_headers_and_body() {
+        let addr = serve(1, HELLO);
+        let transport = ReqwestTransport::default();
+        let (status, headers, body) = current_thread::block_on_all(
+            transport
+                .send(get(format!("http://{}/hello", addr)))
+                .and_then(|res| {
+                    let (status, headers) = (res.status, res.headers);
+                    res.body
+                        .fold(Vec::new(), |mut body, chunk| {
+                            body.extend_from_slice(&chunk);
+                            Ok::<_, OpError>(body)
+                        })
+                        .map(move |body| (status, headers, body))
+                }),
+        )
+        .unwrap();
+
+        assert_eq!(status, 201);
+        assert!(headers.contains(&("x-test".to_owned(), "yes".to_owned())));
+        assert_eq!(&body[..], b"hello");
+    }
+
+    #[test]
+    fn transport_fails_with_network_error() {
+        // Nothing listens on the port once the listener is dropped
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let transport = ReqwestTransport::default();
+        let err = current_thread::block_on_all(transport.send(get(format!("http://{}/", addr))))
+            .err()
+            .unwrap();
+
+        assert_eq!(err.name, "TypeError");
+        assert_eq!(err.code.as_ref().map(String::as_str), Some("ERR_NETWORK"));
+    }
+
+    #[test]
+    fn fetch_reads_status_headers_and_body() {
+        let addr = serve(1, HELLO);
+
+        let mut worker = Worker::new(RuntimeConfig::default());
+        worker
+            .execute(
+                "fetch.js",
+                &format!(
+                    "var result = null;
+                     fetch('http://{}/hello').then(async res => {{
+                         result = {{
+                             status: res.status,
+                             statusText: res.statusText,
+                             ok: res.ok,
+                             header: res.headers.get('X-Test'),
+                             body: await res.text(),
+                         }};
+                     }});",
+                    addr
+                ),
+            )
+            .unwrap();
+        worker.run_until_idle().unwrap();
+        worker
+            .execute(
+                "check.js",
+                "assert(result !== null, 'fetch pending');
+                 assert(result.status === 201 && result.statusText === 'Created', result.status);
+                 assert(result.ok, 'not ok');
+                 assert(result.header === 'yes', result.header);
+                 assert(result.body === 'hello', result.body);",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn fetch_rejects_on_network_error() {
+        // Nothing listens on the port once the listener is dropped
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let mut worker = Worker::new(RuntimeConfig::default());
+        worker
+            .execute(
+                "fetch.js",
+                &format!(
+                    "var error = null;
+                     fetch('http://{}/').catch(e => {{ error = e; }});",
+                    addr
+                ),
+            )
+            .unwrap();
+        worker.run_until_idle().unwrap();
+        worker
+            .execute(
+                "check.js",
+                "assert(error instanceof TypeError, String(error));
+                 assert(error.code === 'ERR_NETWORK', error.code);",
+            )
+            .unwrap();
+    }
 }
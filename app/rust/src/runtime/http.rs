@@ -0,0 +1,255 @@
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::time::Duration;
+
+use futures::{future, Future, Stream};
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+use reqwest::r#async::{Client, ClientBuilder, Decoder};
+use reqwest::{Certificate, Method, Proxy};
+
+use crate::runtime::error::OpError;
+use crate::runtime::resources::ByteStream;
+
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub url: String,
+    pub body: ByteStream,
+}
+
+pub type HttpFuture = Box<dyn Future<Item = HttpResponse, Error = OpError> + Send>;
+
+/// Sends the requests made by `fetch`. Swap it for a stand-in to run scripts
+/// without network access.
+pub trait HttpTransport: Send + Sync {
+    fn send(&self, request: HttpRequest) -> HttpFuture;
+}
+
+/// Options of the reqwest client used by `fetch`.
+#[derive(Clone, Default)]
+pub struct HttpConfig {
+    /// Total time allowed for a request, including reading the body.
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    /// Proxy for all requests, e.g. `http://10.0.2.2:8888`.
+    pub proxy: Option<String>,
+    /// Extra trusted roots as PEM, for example the CA of internal staging servers.
+    pub root_certificates: Vec<Vec<u8>>,
+    pub user_agent: Option<String>,
+}
+
+/// Why `HttpConfig::build` failed.
+#[derive(Debug)]
+pub enum HttpConfigError {
+    InvalidUserAgent(String),
+    /// Bad proxy, certificate, or the client couldn't be set up.
+    Client(reqwest::Error),
+}
+
+impl fmt::Display for HttpConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HttpConfigError::InvalidUserAgent(user_agent) => {
+                write!(f, "Invalid user agent: {}", user_agent)
+            }
+            HttpConfigError::Client(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for HttpConfigError {}
+
+impl From<reqwest::Error> for HttpConfigError {
+    fn from(err: reqwest::Error) -> Self {
+        HttpConfigError::Client(err)
+    }
+}
+
+impl HttpConfig {
+    pub fn build(&self) -> Result<ReqwestTransport, HttpConfigError> {
+        let mut builder = ClientBuilder::new();
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(ref proxy) = self.proxy {
+            builder = builder.proxy(Proxy::all(proxy.as_str())?);
+        }
+        for pem in &self.root_certificates {
+            builder = builder.add_root_certificate(Certificate::from_pem(pem)?);
+        }
+        if let Some(ref user_agent) = self.user_agent {
+            let value = HeaderValue::from_str(user_agent)
+                .map_err(|_| HttpConfigError::InvalidUserAgent(user_agent.clone()))?;
+            let mut headers = HeaderMap::new();
+            headers.insert(USER_AGENT, value);
+            builder = builder.default_headers(headers);
+        }
+
+        Ok(ReqwestTransport {
+            client: builder.build()?,
+        })
+    }
+}
+
+/// The default transport, backed by a reqwest client.
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        ReqwestTransport {
+            client: Client::new(),
+        }
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> HttpFuture {
+        let method = match Method::from_bytes(request.method.as_bytes()) {
+            Ok(method) => method,
+            Err(_) => {
+                let err = OpError::type_error(format!("Invalid method: {}", request.method))
+                    .with_code("ERR_INVALID_METHOD");
+                return Box::new(future::err(err));
+            }
+        };
+
+        let mut builder = self.client.request(method, &request.url);
+        for (name, value) in request.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        if !request.body.is_empty() {
+            builder = builder.body(request.body);
+        }
+
+        Box::new(builder.send().map_err(OpError::from).map(|mut res| {
+            let body = mem::replace(res.body_mut(), Decoder::empty())
+                .map(|chunk| chunk.to_vec().into_boxed_slice())
+                .map_err(OpError::from);
+
+            HttpResponse {
+                status: res.status().as_u16(),
+                headers: res
+                    .headers()
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                        (name.as_str().to_owned(), value)
+                    })
+                    .collect(),
+                url: res.url().as_str().to_owned(),
+                body: Box::new(body),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::{future, stream};
+
+    use super::*;
+    use crate::runtime::config::RuntimeConfig;
+    use crate::runtime::event_loop::Worker;
+
+    /// Answers every request with `status` and `body`, and keeps the requests.
+    pub(crate) struct TestTransport {
+        status: u16,
+        body: &'static str,
+        pub requests: Mutex<Vec<HttpRequest>>,
+    }
+
+    impl TestTransport {
+        pub fn new(status: u16, body: &'static str) -> Arc<Self> {
+            Arc::new(TestTransport {
+                status,
+                body,
+                requests: Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    impl HttpTransport for TestTransport {
+        fn send(&self, request: HttpRequest) -> HttpFuture {
+            let chunk = self.body.as_bytes().to_vec().into_boxed_slice();
+            let response = HttpResponse {
+                status: self.status,
+                headers: vec![("content-type".to_owned(), "text/plain".to_owned())],
+                url: request.url.clone(),
+                body: Box::new(stream::once(Ok(chunk))),
+            };
+            self.requests.lock().unwrap().push(request);
+            Box::new(future::ok(response))
+        }
+    }
+
+    #[test]
+    fn build_fails_on_invalid_user_agent() {
+        let config = HttpConfig {
+            user_agent: Some("native\npractice".to_owned()),
+            ..HttpConfig::default()
+        };
+        match config.build() {
+            Err(HttpConfigError::InvalidUserAgent(user_agent)) => {
+                assert_eq!(user_agent, "native\npractice")
+            }
+            _ => panic!("user agent accepted"),
+        }
+    }
+
+    #[test]
+    fn fetch_sends_through_configured_transport() {
+        let transport = TestTransport::new(404, "missing");
+        let mut worker = Worker::new(RuntimeConfig {
+            http: transport.clone(),
+            ..RuntimeConfig::default()
+        });
+        worker
+            .execute(
+                "fetch.js",
+                "var result = null;
+                 fetch('http://test/items', {
+                     method: 'POST',
+                     headers: { 'X-Test': 'yes' },
+                     body: 'item',
+                 }).then(async res => {
+                     result = { status: res.status, ok: res.ok, body: await res.text() };
+                 });",
+            )
+            .unwrap();
+        worker.run_until_idle().unwrap();
+        worker
+            .execute(
+                "check.js",
+                "assert(result !== null, 'fetch pending');
+                 assert(result.status === 404 && !result.ok, result.status);
+                 assert(result.body === 'missing', result.body);",
+            )
+            .unwrap();
+
+        let requests = transport.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].url, "http://test/items");
+        assert!(requests[0]
+            .headers
+            .iter()
+            .any(|(name, value)| name.eq_ignore_ascii_case("x-test") && value == "yes"));
+        assert_eq!(&requests[0].body[..], b"item");
+    }
+}
@@ -1,33 +1,82 @@
 use std::{
-    sync::atomic::{AtomicUsize, Ordering},
-    sync::Once,
+    collections::HashMap,
+    ffi::{CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    slice,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::{Arc, Mutex},
 };
 
 use futures::stream::{FuturesUnordered, Stream};
+use futures::task::AtomicTask;
 use futures::Async::*;
 use futures::{task, Future, Poll};
-use libc::c_void;
+use libc::{c_char, c_void, size_t};
+use v8::types::{JsFunction, JsNumber, JsObject, Object};
+use v8::Scope;
 
-use crate::runtime::timer::set_timeout;
-use crate::runtime::{eval_script, DenoC, OpAsyncFuture};
+use crate::runtime::config::{RejectionPolicy, RuntimeConfig};
+use crate::runtime::error::{reject_promise, JsError, OpError};
+use crate::runtime::http::HttpTransport;
+use crate::runtime::modules::{DenoSource, ModuleLoader};
+use crate::runtime::resources::{op_close, ResourceTable};
+use crate::runtime::stream_cancel::TimerCancel;
+use crate::runtime::{eval_script, fetch, timer, Buf, DenoC, OpFn};
 
 #[allow(non_camel_case_types)]
-type deno_recv_cb = unsafe extern "C" fn(data: *mut libc::c_void, promise_id: u32, duration: u32);
+type deno_recv_cb = unsafe extern "C" fn(
+    data: *mut libc::c_void,
+    op: *const c_char,
+    promise_id: u32,
+    control: *const u8,
+    len: size_t,
+    zero_copy: *const u8,
+    zero_copy_len: size_t,
+);
+
+#[allow(non_camel_case_types)]
+type deno_load_cb = unsafe extern "C" fn(
+    data: *mut libc::c_void,
+    specifier: *const c_char,
+    referrer: *const c_char,
+    out: *mut DenoSource,
+);
 
 extern "C" {
-    fn deno_init(recv_cb: deno_recv_cb, uuid: u32) -> *const DenoC;
-    fn fire_callback(raw: *const DenoC, promise_id: u32);
+    fn deno_init(recv_cb: deno_recv_cb, load_cb: deno_load_cb, uuid: u32) -> *const DenoC;
+    fn deno_delete(deno: *const DenoC);
+    fn deno_terminate(deno: *const DenoC);
+    fn deno_mod_new(deno: *const DenoC, name: *const c_char, source: *const c_char) -> i32;
+    fn deno_mod_imports_len(deno: *const DenoC, id: i32) -> size_t;
+    fn deno_mod_imports_get(deno: *const DenoC, id: i32, index: size_t) -> *const c_char;
+    fn deno_mod_resolve(deno: *const DenoC, id: i32, specifier: *const c_char, child: i32);
+    fn deno_mod_evaluate(deno: *const DenoC, id: i32) -> bool;
+    fn deno_last_exception(deno: *const DenoC) -> *const c_char;
+    fn deno_take_rejection(deno: *const DenoC) -> bool;
     fn set_deno_data(deno: *const DenoC, user_data: *const libc::c_void);
     fn set_deno_resolver(deno: *const DenoC);
     fn deno_lock(deno: *const DenoC);
     fn deno_unlock(deno: *const DenoC);
+    fn deno_with_scope(deno: *const DenoC, data: *mut c_void, cb: extern "C" fn(*mut c_void));
 }
 
+/// An op future whose failure was already reported to JS.
+type PendingOp = Box<dyn Future<Item = Buf, Error = ()> + Send>;
+
 pub struct Isolate {
     uuid: u32,
     pub deno: *const DenoC,
     pub have_unpolled_ops: bool,
-    pub pending_ops: FuturesUnordered<OpAsyncFuture>,
+    initialized: bool,
+    pub pending_ops: FuturesUnordered<PendingOp>,
+    ops: HashMap<String, OpFn>,
+    pub(crate) resources: ResourceTable,
+    pub(crate) http: Arc<dyn HttpTransport>,
+    cancel_handles: HashMap<u32, TimerCancel>,
+    terminate: TerminateHandle,
+    modules: ModuleLoader,
+    rejection_policy: RejectionPolicy,
+    console_tag: String,
 }
 
 unsafe impl Send for Isolate {}
@@ -35,6 +84,57 @@ unsafe impl Send for Isolate {}
 impl Drop for Isolate {
     fn drop(&mut self) {
         adb_debug!(format!("Isolate {:p} dropped", &self));
+        self.terminate.detach();
+        self.resources.clear();
+        // Globals dropped from now on must not touch the isolate
+        self.with_scope(|scope| v8::global::dispose(scope));
+        unsafe { deno_delete(self.deno) };
+    }
+}
+
+struct DenoPtr(*const DenoC);
+
+unsafe impl Send for DenoPtr {}
+
+struct TerminateState {
+    deno: Mutex<Option<DenoPtr>>,
+    terminated: AtomicBool,
+    task: AtomicTask,
+}
+
+/// Stops an isolate from any thread: running JS is aborted and pending ops are dropped.
+#[derive(Clone)]
+pub struct TerminateHandle {
+    inner: Arc<TerminateState>,
+}
+
+impl TerminateHandle {
+    fn new(deno: *const DenoC) -> Self {
+        TerminateHandle {
+            inner: Arc::new(TerminateState {
+                deno: Mutex::new(Some(DenoPtr(deno))),
+                terminated: AtomicBool::new(false),
+                task: AtomicTask::new(),
+            }),
+        }
+    }
+
+    pub fn terminate(&self) {
+        self.inner.terminated.store(true, Ordering::SeqCst);
+        if let Some(ref deno) = *self.inner.deno.lock().unwrap() {
+            unsafe { deno_terminate(deno.0) };
+        }
+        // Wake the event loop so it notices
+        self.inner.task.notify();
+    }
+
+    pub fn is_terminated(&self) -> bool {
+        self.inner.terminated.load(Ordering::SeqCst)
+    }
+
+    /// Called before the isolate is disposed, later calls to `terminate` only set the flag.
+    fn detach(&self) {
+        self.inner.deno.lock().unwrap().take();
     }
 }
 
@@ -65,22 +165,52 @@ lazy_static! {
     static ref NEXT_RID: AtomicUsize = AtomicUsize::new(0);
 }
 
-static ISOLATE_INIT: Once = Once::new();
+unsafe fn as_slice<'a>(ptr: *const u8, len: size_t) -> &'a [u8] {
+    if ptr.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len)
+    }
+}
 
 impl Isolate {
-    pub fn new() -> Self {
+    pub fn new(config: RuntimeConfig) -> Self {
         let uuid = next_uuid();
-        return Self {
+        let deno = unsafe { deno_init(Self::dispatch, Self::load_module, uuid) };
+        let mut isolate = Self {
             uuid,
-            deno: unsafe { deno_init(Self::dispatch, uuid) },
+            deno,
             have_unpolled_ops: false,
+            initialized: false,
             pending_ops: FuturesUnordered::new(),
+            ops: HashMap::new(),
+            resources: ResourceTable::default(),
+            http: config.http,
+            cancel_handles: HashMap::new(),
+            terminate: TerminateHandle::new(deno),
+            modules: ModuleLoader::new(config.modules),
+            rejection_policy: config.rejection_policy,
+            console_tag: config.console_tag,
         };
+
+        isolate.register_op("setTimeout", timer::op_set_timeout);
+        isolate.register_op("setInterval", timer::op_set_interval);
+        isolate.register_op("clearTimer", timer::op_clear_timer);
+        isolate.register_op("fetch", fetch::op_fetch);
+        isolate.register_op("readBody", fetch::op_read_body);
+        isolate.register_op("abort", fetch::op_abort);
+        isolate.register_op("close", op_close);
+        isolate
+    }
+
+    /// Makes `op` callable from JS as `$dispatch(name, promiseId, control, zeroCopy)`.
+    pub fn register_op(&mut self, name: &str, op: OpFn) {
+        self.ops.insert(name.to_owned(), op);
     }
 
     pub unsafe fn initialize(&mut self) {
         set_deno_data(self.deno, self.as_raw_ptr());
-        eval_script(
+        let ok = eval_script(
             self.deno,
             c_str!("isolate.js"),
             c_str!(
@@ -179,46 +309,294 @@ impl Isolate {
                   const promise_ = Object.assign(promise, methods);
                   promiseTable.set(cmdId, promise_);
 
-                  // Remove the promise
-                  promise.finally(() => {
-                    promiseTable.delete(promise.cmdId);
-                  });
+                  // Remove the promise, without forking an unhandled rejection
+                  const remove = () => promiseTable.delete(cmdId);
+                  promise.then(remove, remove);
 
                   return promise_;
                 }
 
-                function resolve(promiseId, value) {
+                function resolve(promiseId, value, buffer) {
+                  if (promiseTable.has(promiseId)) {
+                    try {
+                      let promise = promiseTable.get(promiseId);
+                      promise.resolve(buffer === undefined ? value : { value, buffer });
+                      if (!promise.repeat) {
+                        promiseTable.delete(promiseId);
+                      }
+                    } catch (e) {
+                      console.log(e.message);
+                    }
+                  }
+                }
+
+                const errorTypes = { Error, TypeError, RangeError, SyntaxError };
+
+                function createError({ name, message, code }) {
+                  const ErrorType = errorTypes[name] || Error;
+                  const error = new ErrorType(message);
+                  error.name = name;
+                  if (code !== undefined) {
+                    error.code = code;
+                  }
+                  return error;
+                }
+
+                function reject(promiseId, error) {
                   if (promiseTable.has(promiseId)) {
                     try {
                       let promise = promiseTable.get(promiseId);
-                      promise.resolve(value);
                       promiseTable.delete(promiseId);
+                      promise.reject(createError(JSON.parse(error)));
                     } catch (e) {
                       console.log(e.message);
                     }
                   }
                 }
 
+                class Headers {
+                  constructor(init) {
+                    this._map = new Map();
+                    if (init instanceof Headers) {
+                      init.forEach((value, name) => this.append(name, value));
+                    } else if (Array.isArray(init)) {
+                      init.forEach(([name, value]) => this.append(name, value));
+                    } else if (init) {
+                      Object.keys(init).forEach(name => this.append(name, init[name]));
+                    }
+                  }
+                  append(name, value) {
+                    name = String(name).toLowerCase();
+                    const current = this._map.get(name);
+                    this._map.set(name, current === undefined ? String(value) : `${current}, ${value}`);
+                  }
+                  set(name, value) {
+                    this._map.set(String(name).toLowerCase(), String(value));
+                  }
+                  get(name) {
+                    const value = this._map.get(String(name).toLowerCase());
+                    return value === undefined ? null : value;
+                  }
+                  has(name) {
+                    return this._map.has(String(name).toLowerCase());
+                  }
+                  delete(name) {
+                    this._map.delete(String(name).toLowerCase());
+                  }
+                  forEach(callback, thisArg) {
+                    this._map.forEach((value, name) => callback.call(thisArg, value, name, this));
+                  }
+                  entries() {
+                    return this._map.entries();
+                  }
+                  keys() {
+                    return this._map.keys();
+                  }
+                  values() {
+                    return this._map.values();
+                  }
+                  [Symbol.iterator]() {
+                    return this._map.entries();
+                  }
+                }
+
+                class AbortSignal {
+                  constructor() {
+                    this.aborted = false;
+                    this.onabort = null;
+                    this._listeners = [];
+                  }
+                  addEventListener(type, listener) {
+                    if (type === 'abort') {
+                      this._listeners.push(listener);
+                    }
+                  }
+                  removeEventListener(type, listener) {
+                    if (type === 'abort') {
+                      this._listeners = this._listeners.filter(l => l !== listener);
+                    }
+                  }
+                  _abort() {
+                    if (this.aborted) return;
+                    this.aborted = true;
+                    const event = { type: 'abort', target: this };
+                    if (typeof this.onabort === 'function') {
+                      this.onabort(event);
+                    }
+                    this._listeners.slice().forEach(listener => listener.call(this, event));
+                  }
+                }
+
+                class AbortController {
+                  constructor() {
+                    this.signal = new AbortSignal();
+                  }
+                  abort() {
+                    this.signal._abort();
+                  }
+                }
+
+                function abortError() {
+                  return createError({
+                    name: 'AbortError',
+                    message: 'The operation was aborted',
+                    code: 'ERR_ABORTED'
+                  });
+                }
+
+                // Dispatch an op whose promise rejects, and whose native future is
+                // dropped, once the signal aborts
+                function dispatchAbortable(op, args, zeroCopy, signal) {
+                  if (signal && signal.aborted) {
+                    return Promise.reject(abortError());
+                  }
+
+                  const promise = createResolvable();
+                  if (signal) {
+                    const onAbort = () => {
+                      $dispatch('abort', promise.cmdId);
+                      promiseTable.delete(promise.cmdId);
+                      promise.reject(abortError());
+                    };
+                    signal.addEventListener('abort', onAbort);
+                    const remove = () => signal.removeEventListener('abort', onAbort);
+                    promise.then(remove, remove);
+                  }
+
+                  $dispatch(op, promise.cmdId, JSON.stringify(args), zeroCopy);
+                  return promise;
+                }
+
+                /** Response body, a stream of ArrayBuffer chunks */
+                class ReadableStream {
+                  constructor(rid, signal) {
+                    this._rid = rid;
+                    // Bodies nobody reads are closed along with their stream
+                    $closeOnCollect(this, rid);
+                    this._signal = signal;
+                    this._closed = false;
+                    this.locked = false;
+                  }
+                  getReader() {
+                    if (this.locked) {
+                      throw new TypeError('ReadableStream is locked');
+                    }
+                    this.locked = true;
+                    return {
+                      read: () => this._read(),
+                      cancel: () => this.cancel(),
+                      releaseLock: () => {
+                        this.locked = false;
+                      }
+                    };
+                  }
+                  cancel() {
+                    if (!this._closed) {
+                      this._closed = true;
+                      $dispatch('close', 0, JSON.stringify({ rid: this._rid }));
+                    }
+                    return Promise.resolve();
+                  }
+                  _read() {
+                    if (this._closed) {
+                      return Promise.resolve({ done: true, value: undefined });
+                    }
+                    return dispatchAbortable('readBody', { rid: this._rid }, undefined, this._signal)
+                      .then(({ value, buffer }) => {
+                        if (JSON.parse(value).done) {
+                          this.cancel();
+                          return { done: true, value: undefined };
+                        }
+                        return { done: false, value: buffer };
+                      }, error => {
+                        this.cancel();
+                        throw error;
+                      });
+                  }
+                  [Symbol.asyncIterator]() {
+                    const reader = this.getReader();
+                    return {
+                      next: () => reader.read(),
+                      return: () => reader.cancel().then(() => ({ done: true, value: undefined })),
+                      [Symbol.asyncIterator]() {
+                        return this;
+                      }
+                    };
+                  }
+                }
+
+                function concatBuffers(chunks) {
+                  const length = chunks.reduce((sum, chunk) => sum + chunk.byteLength, 0);
+                  const result = new Uint8Array(length);
+                  let offset = 0;
+                  chunks.forEach(chunk => {
+                    result.set(new Uint8Array(chunk), offset);
+                    offset += chunk.byteLength;
+                  });
+                  return result.buffer;
+                }
+
                 class Body {
-                  constructor(data) {
-                    this._data = data;
+                  constructor(body) {
+                    this.body = body;
+                    this.bodyUsed = false;
+                  }
+                  arrayBuffer() {
+                    if (this.bodyUsed) {
+                      return Promise.reject(new TypeError('Body has already been consumed'));
+                    }
+                    this.bodyUsed = true;
+
+                    const reader = this.body.getReader();
+                    const chunks = [];
+                    const pump = () => reader.read().then(({ done, value }) => {
+                      if (done) {
+                        return concatBuffers(chunks);
+                      }
+                      chunks.push(value);
+                      return pump();
+                    });
+                    return pump();
                   }
                   text() {
-                    return Promise.resolve(this._data);
+                    return this.arrayBuffer().then(buffer => new TextDecoder().decode(new Uint8Array(buffer)));
                   }
                   json() {
-                    try {
-                      return Promise.resolve(this._data).then(JSON.parse);
-                    } catch (e) {
-                      throw new Error(`Can't not parse json data`);
-                    }
+                    return this.text().then(JSON.parse);
                   }
                 }
 
-                function fetch(url) {
-                  const promise = createResolvable();
-                  $fetch(url, promise.cmdId);
-                  return promise.then(data => new Body(data));
+                class Response extends Body {
+                  constructor(body, { status, statusText, headers, url }) {
+                    super(body);
+                    this.status = status;
+                    this.statusText = statusText;
+                    this.headers = new Headers(headers);
+                    this.url = url;
+                    this.ok = status >= 200 && status < 300;
+                  }
+                }
+
+                function fetch(url, init = {}) {
+                  const method = (init.method || 'GET').toUpperCase();
+                  const headers = new Headers(init.headers);
+                  const signal = init.signal;
+
+                  // Strings and buffers are sent as is, other objects as JSON
+                  let body = init.body;
+                  if (body !== undefined && body !== null && typeof body !== 'string' &&
+                      !(body instanceof ArrayBuffer) && !ArrayBuffer.isView(body)) {
+                    body = JSON.stringify(body);
+                    if (!headers.has('content-type')) {
+                      headers.set('content-type', 'application/json');
+                    }
+                  }
+
+                  const args = { url: String(url), method, headers: [...headers] };
+                  return dispatchAbortable('fetch', args, body, signal).then(value => {
+                    const meta = JSON.parse(value);
+                    return new Response(new ReadableStream(meta.rid, signal), meta);
+                  });
                 }
 
                 let timerMap = new Map();
@@ -226,6 +604,11 @@ impl Isolate {
 
                 // timer implementation
                 async function setTimer(timerId, callback, delay, repeat, ...args) {
+                  delay = Math.max(0, Number(delay) || 0);
+                  if (delay > TIMEOUT_MAX) {
+                    delay = 1;
+                  }
+
                   const timer = {
                     id: timerId,
                     callback,
@@ -233,12 +616,28 @@ impl Isolate {
                     delay
                   };
 
-                  // Add promise to microtask queue
                   timerMap.set(timer.id, timer);
+
+                  if (repeat) {
+                    // Native interval resolves the same cmdId on every tick
+                    const cmdId = nextPromiseId++;
+                    timer.cmdId = cmdId;
+                    promiseTable.set(cmdId, {
+                      cmdId,
+                      repeat: true,
+                      resolve: () => fire(timer.id),
+                      reject: () => timerMap.delete(timer.id)
+                    });
+                    $dispatch('setInterval', cmdId, JSON.stringify({ delay }));
+                    return;
+                  }
+
+                  // Add promise to microtask queue
                   const promise = createResolvable();
+                  timer.cmdId = promise.cmdId;
 
                   // Send message to tokio backend
-                  $newTimer(promise.cmdId, timer.delay);
+                  $dispatch('setTimeout', promise.cmdId, JSON.stringify({ delay }));
 
                   // Wait util promise resolve
                   await promise;
@@ -249,20 +648,12 @@ impl Isolate {
                   if (!timerMap.has(id)) return;
 
                   const timer = timerMap.get(id);
-                  const callback = timer.callback;
-                  callback();
-
                   if (!timer.repeat) {
-                    timeMap.delete(timer.id);
-                    return;
+                    timerMap.delete(timer.id);
                   }
 
-                  // Add new timer (setInterval fake)
-                  const promise = createResolvable();
-                  $newTimer(promise.cmdId, timer.delay, true);
-
-                  await promise;
-                  Promise.resolve(timer.id).then(fire);
+                  const callback = timer.callback;
+                  callback();
                 }
 
                 function setTimeout(callback, delay) {
@@ -284,6 +675,9 @@ impl Isolate {
                     return;
                   }
                   timerMap.delete(timer.id);
+                  // Cancel the pending tokio delay so it won't keep the loop alive
+                  promiseTable.delete(timer.cmdId);
+                  $dispatch('clearTimer', timer.cmdId);
                 }
 
                 function clearInterval(id) {
@@ -357,18 +751,313 @@ impl Isolate {
                     }
                   }
                 };
-                
+
+                /** Console, printed with the priorities of android.util.Log */
+                const LogPriority = { VERBOSE: 2, DEBUG: 3, INFO: 4, WARN: 5, ERROR: 6, FATAL: 7 };
+
+                function inspect(value) {
+                    if (typeof value === 'string') return value;
+                    if (value instanceof Error) return value.stack || String(value);
+                    if (typeof value === 'function') return `[Function: ${value.name || 'anonymous'}]`;
+                    if (typeof value === 'symbol') return value.toString();
+                    if (value !== null && typeof value === 'object') {
+                        try {
+                            return JSON.stringify(value);
+                        } catch (e) {
+                            // circular
+                            return Object.prototype.toString.call(value);
+                        }
+                    }
+                    return String(value);
+                }
+
+                function formatArgs(args) {
+                    return args.map(inspect).join(' ');
+                }
+
+                function renderTable(data) {
+                    if (data === null || typeof data !== 'object') return inspect(data);
+
+                    const columns = [];
+                    let hasValues = false;
+                    const rows = Object.keys(data).map(index => {
+                        const value = data[index];
+                        const cells = { '(index)': index };
+                        if (value !== null && typeof value === 'object') {
+                            for (const key of Object.keys(value)) {
+                                if (!columns.includes(key)) columns.push(key);
+                                cells[key] = inspect(value[key]);
+                            }
+                        } else {
+                            hasValues = true;
+                            cells.Values = inspect(value);
+                        }
+                        return cells;
+                    });
+
+                    const header = ['(index)', ...columns, ...(hasValues ? ['Values'] : [])];
+                    const widths = header.map(column =>
+                        Math.max(column.length, ...rows.map(row => (row[column] || '').length)));
+                    const line = cells => '| ' + cells.map((cell, i) => cell.padEnd(widths[i])).join(' | ') + ' |';
+                    const separator = '|-' + widths.map(width => '-'.repeat(width)).join('-|-') + '-|';
+
+                    return [
+                        line(header),
+                        separator,
+                        ...rows.map(row => line(header.map(column => row[column] || ''))),
+                    ].join('\n');
+                }
+
+                const consoleTimers = new Map();
+
+                this.console = {
+                    log(...args) { $print(LogPriority.INFO, formatArgs(args)); },
+                    info(...args) { $print(LogPriority.INFO, formatArgs(args)); },
+                    debug(...args) { $print(LogPriority.DEBUG, formatArgs(args)); },
+                    warn(...args) { $print(LogPriority.WARN, formatArgs(args)); },
+                    error(...args) { $print(LogPriority.ERROR, formatArgs(args)); },
+                    trace(...args) {
+                        // drop the `Error` line and this frame
+                        const stack = new Error().stack.split('\n').slice(2).join('\n');
+                        $print(LogPriority.VERBOSE, `Trace: ${formatArgs(args)}\n${stack}`);
+                    },
+                    assert(condition, ...args) {
+                        if (condition) return;
+                        const message = args.length ? `: ${formatArgs(args)}` : '';
+                        $print(LogPriority.ERROR, `Assertion failed${message}`);
+                    },
+                    time(label = 'default') {
+                        if (consoleTimers.has(label)) {
+                            $print(LogPriority.WARN, `Timer '${label}' already exists`);
+                            return;
+                        }
+                        consoleTimers.set(label, Date.now());
+                    },
+                    timeEnd(label = 'default') {
+                        if (!consoleTimers.has(label)) {
+                            $print(LogPriority.WARN, `Timer '${label}' does not exist`);
+                            return;
+                        }
+                        const elapsed = Date.now() - consoleTimers.get(label);
+                        consoleTimers.delete(label);
+                        $print(LogPriority.INFO, `${label}: ${elapsed}ms`);
+                    },
+                    table(data) { $print(LogPriority.INFO, renderTable(data)); },
+                };
+
+                /** Global events, the runtime fires `unhandledrejection` */
+                const eventListeners = Object.create(null);
+
+                function addEventListener(type, listener) {
+                    (eventListeners[type] = eventListeners[type] || []).push(listener);
+                }
+
+                function removeEventListener(type, listener) {
+                    const listeners = eventListeners[type] || [];
+                    const index = listeners.indexOf(listener);
+                    if (index !== -1) listeners.splice(index, 1);
+                }
+
+                function dispatchEvent(event) {
+                    for (const listener of (eventListeners[event.type] || []).slice()) {
+                        listener(event);
+                    }
+                    return !event.defaultPrevented;
+                }
+
+                // Called by the runtime, returns true if a listener handled the rejection
+                function dispatchRejection(promise, reason) {
+                    const event = {
+                        type: 'unhandledrejection',
+                        promise,
+                        reason,
+                        defaultPrevented: false,
+                        preventDefault() { this.defaultPrevented = true; },
+                    };
+                    return !dispatchEvent(event);
+                }
+
+                /** CommonJS modules, resolved and compiled by the module loader */
+                const moduleCache = Object.create(null);
+
+                function createRequire(referrer) {
+                    function require(specifier) {
+                        const { filename, wrapper } = $loadModule(specifier, referrer, moduleCache);
+                        if (filename in moduleCache) {
+                            return moduleCache[filename].exports;
+                        }
+
+                        const module = { id: filename, filename, exports: {}, loaded: false };
+                        moduleCache[filename] = module;
+                        const dirname = filename.slice(0, filename.lastIndexOf('/') + 1);
+                        try {
+                            wrapper.call(module.exports, module.exports, createRequire(filename), module, filename, dirname);
+                        } catch (e) {
+                            delete moduleCache[filename];
+                            throw e;
+                        }
+                        module.loaded = true;
+                        return module.exports;
+                    }
+
+                    require.cache = moduleCache;
+                    return require;
+                }
+
+                const require = createRequire(null);
+
         "#
             ),
         );
+        if !ok {
+            adb_debug!(format!("isolate.js failed: {}", self.last_exception()));
+        }
         set_deno_resolver(self.deno);
-    }
 
-    pub fn execute(&mut self, script: &str) {
-        ISOLATE_INIT.call_once(|| unsafe {
-            self.initialize();
+        // Globals dropped on other threads wake the event loop to be released
+        let terminate = self.terminate.clone();
+        let resources = self.resources.clone();
+        self.with_scope(|scope| {
+            v8::global::set_release_notifier(scope, move || terminate.inner.task.notify());
+
+            // $closeOnCollect(owner, rid)
+            let close_on_collect = JsFunction::from_closure(scope, move |info| {
+                let scope = info.scope();
+                match (info.get::<JsObject>(0), info.get::<JsNumber>(1)) {
+                    (Ok(owner), Ok(rid)) => {
+                        resources.close_on_collect(&scope, rid.value() as u32, owner)
+                    }
+                    _ => info.throw_type_error("$closeOnCollect(owner, rid)"),
+                }
+            });
+            JsObject::global(scope).set("$closeOnCollect", close_on_collect);
         });
-        unsafe { eval_script(self.deno, c_str!("worker.js"), c_str!(script)) };
+    }
+
+    /// Runs `f` with the isolate locked, in its context.
+    pub fn with_scope<R, F>(&self, f: F) -> R
+    where
+        F: for<'b> FnOnce(&mut Scope<'b>) -> R,
+    {
+        extern "C" fn run<R, F>(data: *mut c_void)
+        where
+            F: for<'b> FnOnce(&mut Scope<'b>) -> R,
+        {
+            let (f, result) =
+                unsafe { &mut *(data as *mut (Option<F>, Option<std::thread::Result<R>>)) };
+            let f = f.take().unwrap();
+            // Panics can't unwind through V8
+            *result = Some(panic::catch_unwind(AssertUnwindSafe(|| Scope::with(f))));
+        }
+
+        let mut state: (Option<F>, Option<std::thread::Result<R>>) = (Some(f), None);
+        unsafe { deno_with_scope(self.deno, &mut state as *mut _ as *mut c_void, run::<R, F>) };
+        match state.1.unwrap() {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    fn ensure_initialized(&mut self) {
+        // Deferred to the first script, the isolate must not move once JS holds its pointer
+        if !self.initialized {
+            unsafe { self.initialize() };
+            self.initialized = true;
+        }
+    }
+
+    /// Runs `source`, errors and stack traces refer to it as `name`.
+    pub fn execute(&mut self, name: &str, source: &str) -> Result<(), JsError> {
+        self.check_terminated()?;
+        self.ensure_initialized();
+        let name =
+            CString::new(name).map_err(|_| JsError::new("Script name contains a NUL byte"))?;
+        let source =
+            CString::new(source).map_err(|_| JsError::new("Script contains a NUL byte"))?;
+        if !unsafe { eval_script(self.deno, name.as_ptr(), source.as_ptr()) } {
+            return Err(self.last_exception());
+        }
+        self.check_rejections()
+    }
+
+    /// Loads the ES module `specifier` from the module root with its imports and runs it.
+    pub fn execute_module(&mut self, specifier: &str) -> Result<(), JsError> {
+        self.check_terminated()?;
+        self.ensure_initialized();
+
+        let id = self.load_module_graph(specifier, None)?;
+        if !unsafe { deno_mod_evaluate(self.deno, id) } {
+            return Err(self.last_exception());
+        }
+        self.check_rejections()
+    }
+
+    /// Compiles the module and, recursively, its imports. Returns the module id.
+    fn load_module_graph(
+        &mut self,
+        specifier: &str,
+        referrer: Option<&str>,
+    ) -> Result<i32, JsError> {
+        let filename = self.modules.load(specifier, referrer)?;
+        if let Some(id) = self.modules.module_id(&filename) {
+            return Ok(id);
+        }
+
+        let name = CString::new(filename.as_str()).unwrap();
+        let source = self.modules.source(&filename);
+        let id = unsafe { deno_mod_new(self.deno, name.as_ptr(), source.as_ptr()) };
+        if id == 0 {
+            return Err(self.last_exception());
+        }
+        // Registered before the imports are loaded, for circular imports
+        self.modules.register_module(filename.clone(), id);
+
+        let len = unsafe { deno_mod_imports_len(self.deno, id) };
+        for index in 0..len {
+            let import = unsafe { CStr::from_ptr(deno_mod_imports_get(self.deno, id, index)) };
+            let child = self.load_module_graph(&import.to_string_lossy(), Some(&filename))?;
+            unsafe { deno_mod_resolve(self.deno, id, import.as_ptr(), child) };
+        }
+        Ok(id)
+    }
+
+    fn check_terminated(&self) -> Result<(), JsError> {
+        if self.terminate.is_terminated() {
+            Err(JsError::new("Script execution was terminated"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn last_exception(&self) -> JsError {
+        let json = unsafe { CStr::from_ptr(deno_last_exception(self.deno)) };
+        JsError::from_json(&json.to_string_lossy())
+    }
+
+    /// Applies the rejection policy to promises rejected without a handler, unless a
+    /// JS `unhandledrejection` listener prevented it.
+    fn check_rejections(&mut self) -> Result<(), JsError> {
+        while unsafe { deno_take_rejection(self.deno) } {
+            let err = self.last_exception();
+            match self.rejection_policy {
+                RejectionPolicy::Error => return Err(err),
+                RejectionPolicy::Log => log::error!("Uncaught (in promise) {}", err),
+                RejectionPolicy::Terminate => {
+                    self.terminate.terminate();
+                    return Err(err);
+                }
+                RejectionPolicy::Callback(ref callback) => callback(&err),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn console_tag(&self) -> &str {
+        &self.console_tag
+    }
+
+    pub fn terminate_handle(&self) -> TerminateHandle {
+        self.terminate.clone()
     }
 
     #[inline]
@@ -382,29 +1071,90 @@ impl Isolate {
         self as *const _ as *const c_void
     }
 
-    extern "C" fn dispatch(data: *mut libc::c_void, promise_id: u32, delay: u32) {
-        let isolate = unsafe { Isolate::from_raw_ptr(data) };
-        let (task, _trigger) = set_timeout(delay);
+    /// Keeps the cancel trigger of the op dispatched for `promise_id`.
+    pub(crate) fn add_cancel_handle(&mut self, promise_id: u32, trigger: TimerCancel) {
+        self.cancel_handles.insert(promise_id, trigger);
+    }
+
+    /// Cancels the pending op dispatched for `promise_id` (a timer, a fetch, ...), if any.
+    pub fn cancel_op(&mut self, promise_id: u32) {
+        // Dropping the trigger completes the op without resolving its promise
+        self.cancel_handles.remove(&promise_id);
+    }
+
+    /// Runs the op registered as `name` and queues its future.
+    pub fn dispatch_op(&mut self, name: &str, promise_id: u32, control: &[u8], zero_copy: &[u8]) {
+        let op = match self.ops.get(name) {
+            Some(op) => *op,
+            None => {
+                let err = OpError::type_error(format!("Unknown op: {}", name))
+                    .with_code("ERR_UNKNOWN_OP");
+                reject_promise(self.deno, promise_id, &err);
+                return;
+            }
+        };
 
-        let deno = unsafe { isolate.deno.as_ref() };
-        let task = task.and_then(move |_| {
-            let deno = deno.unwrap();
-            unsafe { fire_callback(deno, promise_id) };
-            Ok(vec![1u8].into_boxed_slice())
+        let deno = unsafe { self.deno.as_ref() };
+        let task = op(self, promise_id, control, zero_copy).or_else(move |err| {
+            reject_promise(deno.unwrap(), promise_id, &err);
+            Ok(Buf::default())
         });
+        self.pending_ops.push(Box::new(task));
+        self.have_unpolled_ops = true;
+    }
 
-        isolate.pending_ops.push(Box::new(task));
-        isolate.have_unpolled_ops = true;
+    extern "C" fn dispatch(
+        data: *mut libc::c_void,
+        op: *const c_char,
+        promise_id: u32,
+        control: *const u8,
+        len: size_t,
+        zero_copy: *const u8,
+        zero_copy_len: size_t,
+    ) {
+        let isolate = unsafe { Isolate::from_raw_ptr(data) };
+        let name = unsafe { rust_str!(op) };
+        let control = unsafe { as_slice(control, len) };
+        let zero_copy = unsafe { as_slice(zero_copy, zero_copy_len) };
+        isolate.dispatch_op(name, promise_id, control, zero_copy);
+    }
+
+    extern "C" fn load_module(
+        data: *mut libc::c_void,
+        specifier: *const c_char,
+        referrer: *const c_char,
+        out: *mut DenoSource,
+    ) {
+        let isolate = unsafe { Isolate::from_raw_ptr(data) };
+        let specifier = unsafe { rust_str!(specifier) };
+        let referrer = if referrer.is_null() {
+            None
+        } else {
+            Some(unsafe { rust_str!(referrer) })
+        };
+        isolate
+            .modules
+            .load_into(specifier, referrer, unsafe { &mut *out });
     }
 }
 
 impl Future for Isolate {
     type Item = ();
-    type Error = ();
+    type Error = JsError;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.terminate.inner.task.register();
+        if self.terminate.is_terminated() {
+            // Dropping the ops cancels their timers and requests
+            self.pending_ops = FuturesUnordered::new();
+            self.cancel_handles.clear();
+            self.resources.clear();
+            return Ok(Ready(()));
+        }
+
         // Lock the current thread for V8.
         let _locker = LockerScope::new(self.deno);
+        self.with_scope(|scope| v8::global::release_pending(scope));
 
         loop {
             self.have_unpolled_ops = false;
@@ -414,11 +1164,16 @@ impl Future for Isolate {
                 Ok(Ready(None)) => break,
                 Ok(NotReady) => break,
                 Ok(Ready(Some(_buf))) => {
+                    // Forget the triggers of ops that already completed
+                    self.cancel_handles.retain(|_, trigger| !trigger.is_done());
                     break;
                 }
             }
         }
 
+        // Callbacks run by the ops may have left rejected promises behind
+        self.check_rejections()?;
+
         // We're idle if pending_ops is empty.
         if self.pending_ops.is_empty() {
             Ok(Ready(()))
@@ -430,3 +1185,68 @@ impl Future for Isolate {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::runtime::config::RuntimeConfig;
+    use crate::runtime::event_loop::Worker;
+
+    #[test]
+    fn run_until_idle_ends_once_ops_settled() {
+        let mut worker = Worker::new(RuntimeConfig::default());
+        worker
+            .execute(
+                "idle.js",
+                "var done = false;
+                 setTimeout(() => setTimeout(() => { done = true; }, 10), 10);",
+            )
+            .unwrap();
+        worker.run_until_idle().unwrap();
+        worker
+            .execute("check.js", "assert(done, 'nested timeout pending')")
+            .unwrap();
+    }
+
+    #[test]
+    fn run_until_idle_ends_without_ops() {
+        let mut worker = Worker::new(RuntimeConfig::default());
+        worker.execute("empty.js", "1 + 1").unwrap();
+        worker.run_until_idle().unwrap();
+    }
+
+    #[test]
+    fn run_until_idle_ends_on_terminate() {
+        let mut worker = Worker::new(RuntimeConfig::default());
+        worker
+            .execute("forever.js", "setInterval(() => {}, 5)")
+            .unwrap();
+
+        let handle = worker.clone();
+        let terminate = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            handle.terminate();
+        });
+        let start = Instant::now();
+        worker.run_until_idle().unwrap();
+        terminate.join().unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(worker.execute("late.js", "1").is_err());
+    }
+
+    #[test]
+    fn run_until_idle_fails_on_unhandled_rejection() {
+        let mut worker = Worker::new(RuntimeConfig::default());
+        worker
+            .execute(
+                "reject.js",
+                "setTimeout(() => Promise.reject(new Error('boom')), 5)",
+            )
+            .unwrap();
+        let err = worker.run_until_idle().unwrap_err();
+        assert!(err.message.contains("boom"), "{}", err);
+    }
+}
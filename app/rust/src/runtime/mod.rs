@@ -1,12 +1,23 @@
 use futures::Future;
 use libc::c_char;
+use serde::de::DeserializeOwned;
 use tokio::runtime;
 
+use crate::runtime::error::OpError;
+use crate::runtime::isolate::Isolate;
+
+pub mod config;
+pub mod console;
+pub mod error;
 pub mod event_loop;
 pub mod fetch;
+pub mod http;
 pub mod isolate;
+pub mod modules;
+pub mod resources;
 pub mod stream_cancel;
 pub mod timer;
+#[cfg(feature = "android")]
 pub mod ui_thread;
 pub mod util;
 
@@ -17,7 +28,7 @@ pub struct DenoC {
 
 #[allow(non_snake_case)]
 extern "C" {
-    fn eval_script(d: *const DenoC, name: *const c_char, script: *const c_char);
+    fn eval_script(d: *const DenoC, name: *const c_char, script: *const c_char) -> bool;
 }
 
 fn create_thread_pool_runtime() -> tokio::runtime::Runtime {
@@ -32,4 +43,25 @@ fn create_thread_pool_runtime() -> tokio::runtime::Runtime {
 }
 
 pub type Buf = Box<[u8]>;
-pub type OpAsyncFuture = Box<dyn Future<Item = Buf, Error = ()> + Send>;
+/// An op future, its error rejects the promise the op was dispatched for.
+pub type OpAsyncFuture = Box<dyn Future<Item = Buf, Error = OpError> + Send>;
+
+/// A native op. `control` holds the arguments passed to `$dispatch` from JS and
+/// `zero_copy` an optional payload such as a request body.
+pub type OpFn =
+    fn(isolate: &mut Isolate, promise_id: u32, control: &[u8], zero_copy: &[u8]) -> OpAsyncFuture;
+
+/// Decodes the JSON arguments of an op.
+pub(crate) fn op_args<T: DeserializeOwned>(control: &[u8]) -> Result<T, OpError> {
+    serde_json::from_slice(control).map_err(OpError::from)
+}
+
+/// An op which completed synchronously.
+pub(crate) fn op_ok() -> OpAsyncFuture {
+    Box::new(futures::future::ok(Buf::default()))
+}
+
+/// An op which failed synchronously.
+pub(crate) fn op_err(err: OpError) -> OpAsyncFuture {
+    Box::new(futures::future::err(err))
+}
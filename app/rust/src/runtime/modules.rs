@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use libc::c_char;
+
+/// Where module sources are read from. Paths are `/` separated and relative to the root.
+pub trait ModuleSource: Send + Sync {
+    fn read(&self, path: &str) -> io::Result<String>;
+}
+
+/// Reads modules from a directory, for host builds and tests.
+pub struct FsModuleSource {
+    root: PathBuf,
+}
+
+impl FsModuleSource {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        FsModuleSource { root: root.into() }
+    }
+}
+
+impl ModuleSource for FsModuleSource {
+    fn read(&self, path: &str) -> io::Result<String> {
+        let path = self.root.join(path);
+        if path.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "is a directory"));
+        }
+        fs::read_to_string(path)
+    }
+}
+
+#[derive(Debug)]
+pub struct ModuleError {
+    pub specifier: String,
+    pub referrer: Option<String>,
+    pub message: String,
+}
+
+impl ModuleError {
+    pub fn new(specifier: &str, referrer: Option<&str>, message: &str) -> Self {
+        ModuleError {
+            specifier: specifier.to_owned(),
+            referrer: referrer.map(str::to_owned),
+            message: message.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cannot load module '{}'", self.specifier)?;
+        if let Some(ref referrer) = self.referrer {
+            write!(f, " from '{}'", referrer)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Filled for `$loadModule`, must match `deno_source` in api.h.
+#[repr(C)]
+pub struct DenoSource {
+    pub filename: *const c_char,
+    pub source: *const c_char,
+    pub error: *const c_char,
+}
+
+/// Resolves specifiers to paths below the root and caches their sources and compiled
+/// ES modules.
+///
+/// `./` and `../` are relative to the importing module, anything else is relative to
+/// the root. `.js` and `/index.js` are tried when the path itself doesn't exist.
+pub struct ModuleLoader {
+    source: Arc<dyn ModuleSource>,
+    // filename -> source, kept as C strings since `$loadModule` borrows them
+    sources: HashMap<String, (CString, CString)>,
+    // filename -> id of the compiled ES module
+    modules: HashMap<String, i32>,
+    last_error: CString,
+}
+
+impl ModuleLoader {
+    pub fn new(source: Arc<dyn ModuleSource>) -> Self {
+        ModuleLoader {
+            source,
+            sources: HashMap::new(),
+            modules: HashMap::new(),
+            last_error: CString::default(),
+        }
+    }
+
+    pub fn resolve(&self, specifier: &str, referrer: Option<&str>) -> Result<String, ModuleError> {
+        let err = |message: &str| ModuleError::new(specifier, referrer, message);
+
+        let mut parts: Vec<&str> = Vec::new();
+        if specifier.starts_with("./") || specifier.starts_with("../") {
+            if let Some(referrer) = referrer {
+                parts.extend(referrer.split('/'));
+                // Drop the referrer's filename
+                parts.pop();
+            }
+        }
+        for part in specifier.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    if parts.pop().is_none() {
+                        return Err(err("path is outside of the module root"));
+                    }
+                }
+                part => parts.push(part),
+            }
+        }
+        if parts.is_empty() {
+            return Err(err("empty module path"));
+        }
+        Ok(parts.join("/"))
+    }
+
+    /// Resolves `specifier` and reads its source into the cache, returns the filename.
+    pub fn load(&mut self, specifier: &str, referrer: Option<&str>) -> Result<String, ModuleError> {
+        let path = self.resolve(specifier, referrer)?;
+        let candidates = [
+            path.clone(),
+            format!("{}.js", path),
+            format!("{}/index.js", path),
+        ];
+
+        for filename in candidates.iter() {
+            if !self.sources.contains_key(filename) {
+                let source = match self.source.read(filename) {
+                    Ok(source) => source,
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(ModuleError::new(specifier, referrer, &e.to_string())),
+                };
+                let source = CString::new(source).map_err(|_| {
+                    ModuleError::new(specifier, referrer, "source contains a NUL byte")
+                })?;
+                let name = CString::new(filename.as_str()).unwrap();
+                self.sources.insert(filename.clone(), (name, source));
+            }
+            return Ok(filename.clone());
+        }
+
+        Err(ModuleError::new(specifier, referrer, "module not found"))
+    }
+
+    /// The source of a module returned by `load`.
+    pub fn source(&self, filename: &str) -> &CStr {
+        &self.sources[filename].1
+    }
+
+    pub fn module_id(&self, filename: &str) -> Option<i32> {
+        self.modules.get(filename).cloned()
+    }
+
+    pub fn register_module(&mut self, filename: String, id: i32) {
+        self.modules.insert(filename, id);
+    }
+
+    /// Answers `$loadModule`, the pointers stay valid as long as the loader lives.
+    pub fn load_into(&mut self, specifier: &str, referrer: Option<&str>, out: &mut DenoSource) {
+        match self.load(specifier, referrer) {
+            Ok(filename) => {
+                let (ref name, ref source) = self.sources[&filename];
+                out.filename = name.as_ptr();
+                out.source = source.as_ptr();
+            }
+            Err(err) => {
+                adb_debug!(format!("{}", err));
+                self.last_error = CString::new(err.to_string()).unwrap_or_default();
+                out.error = self.last_error.as_ptr();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "android")]
+pub use self::assets::AssetModuleSource;
+
+#[cfg(feature = "android")]
+mod assets {
+    use std::ffi::CString;
+    use std::io;
+    use std::slice;
+
+    use jni::objects::{GlobalRef, JObject};
+    use jni::JNIEnv;
+    use libc::{c_char, c_int, c_void, off_t};
+
+    use super::ModuleSource;
+
+    #[repr(C)]
+    struct AAssetManager {
+        _unused: [u8; 0],
+    }
+
+    #[repr(C)]
+    struct AAsset {
+        _unused: [u8; 0],
+    }
+
+    const AASSET_MODE_BUFFER: c_int = 3;
+
+    extern "C" {
+        fn AAssetManager_fromJava(
+            env: *mut jni::sys::JNIEnv,
+            asset_manager: jni::sys::jobject,
+        ) -> *mut AAssetManager;
+        fn AAssetManager_open(
+            manager: *mut AAssetManager,
+            filename: *const c_char,
+            mode: c_int,
+        ) -> *mut AAsset;
+        fn AAsset_getBuffer(asset: *mut AAsset) -> *const c_void;
+        fn AAsset_getLength(asset: *mut AAsset) -> off_t;
+        fn AAsset_close(asset: *mut AAsset);
+    }
+
+    /// Reads modules from the APK assets.
+    pub struct AssetModuleSource {
+        manager: *mut AAssetManager,
+        // Keeps the Java AssetManager, and so `manager`, alive
+        _assets: GlobalRef,
+    }
+
+    // AAssetManager is thread-safe
+    unsafe impl Send for AssetModuleSource {}
+    unsafe impl Sync for AssetModuleSource {}
+
+    impl AssetModuleSource {
+        pub fn new(env: &JNIEnv, assets: JObject) -> jni::errors::Result<Self> {
+            let assets = env.new_global_ref(assets)?;
+            let manager = unsafe {
+                AAssetManager_fromJava(env.get_native_interface(), assets.as_obj().into_inner())
+            };
+            Ok(AssetModuleSource {
+                manager,
+                _assets: assets,
+            })
+        }
+    }
+
+    impl ModuleSource for AssetModuleSource {
+        fn read(&self, path: &str) -> io::Result<String> {
+            let filename =
+                CString::new(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            unsafe {
+                let asset = AAssetManager_open(self.manager, filename.as_ptr(), AASSET_MODE_BUFFER);
+                if asset.is_null() {
+                    return Err(io::Error::new(io::ErrorKind::NotFound, path));
+                }
+                let buffer = AAsset_getBuffer(asset) as *const u8;
+                let source = if buffer.is_null() {
+                    // Out of memory, or the asset couldn't be mapped
+                    Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("cannot read asset {}", path),
+                    ))
+                } else {
+                    let data = slice::from_raw_parts(buffer, AAsset_getLength(asset) as usize);
+                    String::from_utf8(data.to_vec())
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                };
+                AAsset_close(asset);
+                source
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::Arc;
+
+    use super::{FsModuleSource, ModuleLoader};
+
+    /// A module root below the temp dir, with the given files.
+    fn root(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = env::temp_dir().join(format!("modules-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (path, source) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        root
+    }
+
+    fn loader(root: &PathBuf) -> ModuleLoader {
+        ModuleLoader::new(Arc::new(FsModuleSource::new(root.clone())))
+    }
+
+    #[test]
+    fn resolves_relative_to_the_referrer() {
+        let loader = loader(&PathBuf::new());
+        let resolve = |specifier, referrer| loader.resolve(specifier, referrer).unwrap();
+
+        assert_eq!(resolve("./b.js", Some("lib/a.js")), "lib/b.js");
+        assert_eq!(resolve("../b.js", Some("lib/a/a.js")), "lib/b.js");
+        assert_eq!(resolve("./b.js", None), "b.js");
+        // Bare specifiers are relative to the root
+        assert_eq!(resolve("util/b.js", Some("lib/a.js")), "util/b.js");
+        assert_eq!(resolve("/util/./b.js", Some("lib/a.js")), "util/b.js");
+    }
+
+    #[test]
+    fn rejects_paths_outside_of_the_root() {
+        let loader = loader(&PathBuf::new());
+
+        let err = loader.resolve("../../b.js", Some("lib/a.js")).unwrap_err();
+        assert_eq!(err.message, "path is outside of the module root");
+        assert!(loader.resolve("../b.js", None).is_err());
+        assert!(loader.resolve("./", Some("a.js")).is_err());
+    }
+
+    #[test]
+    fn loads_with_js_and_index_fallbacks() {
+        let root = root(
+            "fallbacks",
+            &[
+                ("main.js", "main"),
+                ("util.js", "util"),
+                ("lib/index.js", "lib"),
+            ],
+        );
+        let mut loader = loader(&root);
+
+        assert_eq!(loader.load("./main.js", None).unwrap(), "main.js");
+        assert_eq!(loader.load("./util", Some("main.js")).unwrap(), "util.js");
+        assert_eq!(
+            loader.load("./lib", Some("main.js")).unwrap(),
+            "lib/index.js"
+        );
+        assert_eq!(loader.source("lib/index.js").to_str().unwrap(), "lib");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn caches_loaded_sources() {
+        let root = root("cache", &[("main.js", "first")]);
+        let mut loader = loader(&root);
+
+        assert_eq!(loader.load("main", None).unwrap(), "main.js");
+        fs::write(root.join("main.js"), "second").unwrap();
+        assert_eq!(loader.load("./main.js", None).unwrap(), "main.js");
+        assert_eq!(loader.source("main.js").to_str().unwrap(), "first");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn errors_name_the_specifier_and_referrer() {
+        let root = root("missing", &[("main.js", "")]);
+        let mut loader = loader(&root);
+
+        let err = loader.load("./missing", Some("main.js")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot load module './missing' from 'main.js': module not found"
+        );
+        let err = loader.load("missing", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot load module 'missing': module not found"
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
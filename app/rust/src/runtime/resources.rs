@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+use futures::Stream;
+use serde_derive::Deserialize;
+use v8::types::{Handle, JsObject};
+use v8::{Scope, Weak};
+
+use crate::runtime::error::OpError;
+use crate::runtime::isolate::Isolate;
+use crate::runtime::{op_args, op_err, op_ok, Buf, OpAsyncFuture};
+
+pub type ByteStream = Box<dyn Stream<Item = Buf, Error = OpError> + Send>;
+
+#[derive(Deserialize)]
+pub(crate) struct ResourceArgs {
+    pub rid: u32,
+}
+
+#[derive(Default)]
+struct Resources {
+    next_rid: u32,
+    streams: HashMap<u32, Arc<Mutex<ByteStream>>>,
+    // JS objects whose collection closes the resource
+    owners: HashMap<u32, Weak<JsObject>>,
+}
+
+/// Byte streams (e.g. response bodies) which outlive the op that opened them.
+/// JS refers to them by resource id until it dispatches `close`, or until the owner
+/// set by `close_on_collect` is collected.
+#[derive(Clone, Default)]
+pub struct ResourceTable {
+    inner: Arc<Mutex<Resources>>,
+}
+
+impl ResourceTable {
+    pub fn add(&self, stream: ByteStream) -> u32 {
+        let mut resources = self.inner.lock().unwrap();
+        resources.next_rid += 1;
+        let rid = resources.next_rid;
+        resources.streams.insert(rid, Arc::new(Mutex::new(stream)));
+        rid
+    }
+
+    pub fn get(&self, rid: u32) -> Result<Arc<Mutex<ByteStream>>, OpError> {
+        let resources = self.inner.lock().unwrap();
+        resources.streams.get(&rid).cloned().ok_or_else(|| {
+            OpError::type_error(format!("Bad resource id: {}", rid)).with_code("ERR_BAD_RESOURCE")
+        })
+    }
+
+    pub fn close(&self, rid: u32) -> bool {
+        let (stream, _owner) = {
+            let mut resources = self.inner.lock().unwrap();
+            (
+                resources.streams.remove(&rid),
+                resources.owners.remove(&rid),
+            )
+        };
+        stream.is_some()
+    }
+
+    /// Closes `rid` once `owner` is collected, if it is still open then.
+    pub fn close_on_collect<'a>(&self, scope: &Scope<'a>, rid: u32, owner: Handle<'a, JsObject>) {
+        // The finalizer must not keep the table, which holds the finalizer, alive
+        let table = Arc::downgrade(&self.inner);
+        let owner = Weak::new(scope, owner, move || {
+            if let Some(inner) = table.upgrade() {
+                ResourceTable { inner }.close(rid);
+            }
+        });
+        let mut resources = self.inner.lock().unwrap();
+        if resources.streams.contains_key(&rid) {
+            resources.owners.insert(rid, owner);
+        }
+    }
+
+    /// Closes every resource, e.g. once the isolate was terminated.
+    pub fn clear(&self) {
+        let _closed = {
+            let mut resources = self.inner.lock().unwrap();
+            (
+                mem::take(&mut resources.streams),
+                mem::take(&mut resources.owners),
+            )
+        };
+    }
+}
+
+pub fn op_close(
+    isolate: &mut Isolate,
+    _promise_id: u32,
+    control: &[u8],
+    _zero_copy: &[u8],
+) -> OpAsyncFuture {
+    match op_args::<ResourceArgs>(control) {
+        Ok(args) => {
+            isolate.resources.close(args.rid);
+            op_ok()
+        }
+        Err(err) => op_err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+
+    use super::*;
+
+    fn empty_body() -> ByteStream {
+        Box::new(stream::empty())
+    }
+
+    #[test]
+    fn clear_closes_every_resource() {
+        let resources = ResourceTable::default();
+        let first = resources.add(empty_body());
+        let second = resources.add(empty_body());
+
+        resources.clear();
+        assert!(resources.get(first).is_err());
+        assert!(!resources.close(second));
+        // Ids aren't reused
+        assert!(resources.add(empty_body()) > second);
+    }
+}
@@ -48,9 +48,38 @@ where
     }
 }
 
+/// Wraps `future` so it completes with `None`, leaving `future` unfinished, as
+/// soon as the returned `TimerCancel` is dropped.
+pub fn cancelable<F>(
+    future: F,
+) -> (
+    impl Future<Item = Option<F::Item>, Error = F::Error>,
+    TimerCancel,
+)
+where
+    F: Future,
+{
+    let (tx, rx) = oneshot::channel::<()>();
+    let task = future
+        .into_stream()
+        .take_until(rx.map_err(|_| ()))
+        .into_future()
+        .map(|(item, _)| item)
+        .map_err(|(err, _)| err);
+
+    (task, TimerCancel(Some(tx)))
+}
+
 #[derive(Debug)]
 pub struct TimerCancel(pub Option<oneshot::Sender<()>>);
 
+impl TimerCancel {
+    /// Whether the guarded future has already completed and dropped its receiver.
+    pub fn is_done(&self) -> bool {
+        self.0.as_ref().map_or(true, |tx| tx.is_canceled())
+    }
+}
+
 impl Drop for TimerCancel {
     fn drop(&mut self) {
         if let Some(tx) = self.0.take() {
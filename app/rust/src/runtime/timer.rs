@@ -1,26 +1,197 @@
 use std::convert::Into;
 use std::time::{Duration, Instant};
 
-use futures::Future;
-use tokio_timer::Delay;
+use futures::{Future, Stream};
+use serde_derive::Deserialize;
+use tokio_timer::{Delay, Interval};
 
-use crate::runtime::stream_cancel::TimerCancel;
+use crate::runtime::error::OpError;
+use crate::runtime::isolate::Isolate;
+use crate::runtime::stream_cancel::{cancelable, StreamExt, TimerCancel};
+use crate::runtime::{op_args, op_err, op_ok, DenoC, OpAsyncFuture};
 
-pub(crate) fn panic_on_error<I, E, F>(f: F) -> impl Future<Item = I, Error = ()>
-where
-    F: Future<Item = I, Error = E>,
-    E: std::fmt::Debug,
-{
-    f.map_err(|err| adb_debug!(format!("Future got unexpected error: {:?}", err)))
+extern "C" {
+    fn fire_callback(raw: *const DenoC, promise_id: u32);
 }
 
-pub fn set_timeout(delay: u32) -> (impl Future<Item = (), Error = ()>, TimerCancel) {
-    let (tx, _rx) = futures::sync::oneshot::channel::<()>();
+#[derive(Deserialize)]
+struct TimerArgs {
+    delay: u32,
+}
+
+/// Resolves with `true` once `delay` elapsed, or with `false` as soon as the
+/// returned `TimerCancel` is dropped.
+pub fn set_timeout(delay: u32) -> (impl Future<Item = bool, Error = OpError>, TimerCancel) {
     let duration = Duration::from_millis(delay.into());
+    let (delay_task, trigger) =
+        cancelable(Delay::new(Instant::now() + duration).map_err(OpError::from));
+
+    (delay_task.map(|fired| fired.is_some()), trigger)
+}
+
+/// Ticks every `delay` ms until the returned `TimerCancel` is dropped.
+pub fn set_interval(delay: u32) -> (impl Stream<Item = (), Error = OpError>, TimerCancel) {
+    let (tx, rx) = futures::sync::oneshot::channel::<()>();
+    // Interval panics on a zero period
+    let duration = Duration::from_millis(delay.max(1).into());
+
+    let ticks = Interval::new(Instant::now() + duration, duration)
+        .map(|_| ())
+        .map_err(OpError::from)
+        .take_until(rx.map_err(|_| ()));
+
+    (ticks, TimerCancel(Some(tx)))
+}
+
+pub fn op_set_timeout(
+    isolate: &mut Isolate,
+    promise_id: u32,
+    control: &[u8],
+    _zero_copy: &[u8],
+) -> OpAsyncFuture {
+    let args: TimerArgs = match op_args(control) {
+        Ok(args) => args,
+        Err(err) => return op_err(err),
+    };
+
+    let (task, trigger) = set_timeout(args.delay);
+    isolate.add_cancel_handle(promise_id, trigger);
+
+    let deno = unsafe { isolate.deno.as_ref() };
+    Box::new(task.and_then(move |fired| {
+        if fired {
+            let deno = deno.unwrap();
+            unsafe { fire_callback(deno, promise_id) };
+        }
+        Ok(vec![fired as u8].into_boxed_slice())
+    }))
+}
+
+/// Fires `promise_id` every `delay` ms until it is cleared.
+pub fn op_set_interval(
+    isolate: &mut Isolate,
+    promise_id: u32,
+    control: &[u8],
+    _zero_copy: &[u8],
+) -> OpAsyncFuture {
+    let args: TimerArgs = match op_args(control) {
+        Ok(args) => args,
+        Err(err) => return op_err(err),
+    };
+
+    let (ticks, trigger) = set_interval(args.delay);
+    isolate.add_cancel_handle(promise_id, trigger);
+
+    let deno = unsafe { isolate.deno.as_ref() };
+    Box::new(
+        ticks
+            .for_each(move |_| {
+                let deno = deno.unwrap();
+                unsafe { fire_callback(deno, promise_id) };
+                Ok(())
+            })
+            .map(|_| vec![0u8].into_boxed_slice()),
+    )
+}
+
+pub fn op_clear_timer(
+    isolate: &mut Isolate,
+    promise_id: u32,
+    _control: &[u8],
+    _zero_copy: &[u8],
+) -> OpAsyncFuture {
+    isolate.cancel_op(promise_id);
+    op_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::{Future, Stream};
+    use tokio::runtime::current_thread;
+
+    use super::{set_interval, set_timeout};
+    use crate::runtime::config::RuntimeConfig;
+    use crate::runtime::event_loop::Worker;
+
+    #[test]
+    fn timeouts_fire_in_delay_order() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (slow, _slow_cancel) = set_timeout(30);
+        let (fast, _fast_cancel) = set_timeout(10);
+
+        let (slow_order, fast_order) = (order.clone(), order.clone());
+        let both = slow
+            .map(move |fired| slow_order.lock().unwrap().push((30, fired)))
+            .join(fast.map(move |fired| fast_order.lock().unwrap().push((10, fired))));
+        current_thread::block_on_all(both).unwrap();
+
+        assert_eq!(*order.lock().unwrap(), vec![(10, true), (30, true)]);
+    }
+
+    #[test]
+    fn dropped_timeout_resolves_unfired() {
+        let (timeout, cancel) = set_timeout(10_000);
+        drop(cancel);
+        assert_eq!(current_thread::block_on_all(timeout).unwrap(), false);
+    }
+
+    #[test]
+    fn interval_ticks_until_cancelled() {
+        let (ticks, cancel) = set_interval(5);
+        let ticks = current_thread::block_on_all(ticks.take(3).collect()).unwrap();
+        assert_eq!(ticks.len(), 3);
+        drop(cancel);
+
+        let (ticks, cancel) = set_interval(5);
+        drop(cancel);
+        let ticks = current_thread::block_on_all(ticks.collect()).unwrap();
+        assert!(ticks.is_empty());
+    }
 
-    let delay_task = panic_on_error(Delay::new(Instant::now() + duration))
-        // .select(rx.map_err(|_| ()))
-        .then(|_| Ok(()));
+    #[test]
+    fn js_timeouts_run_in_delay_order() {
+        let mut worker = Worker::new(RuntimeConfig::default());
+        worker
+            .execute(
+                "timers.js",
+                "var order = [];
+                 setTimeout(() => order.push('slow'), 30);
+                 setTimeout(() => order.push('fast'), 10);
+                 setTimeout(() => order.push('now'));",
+            )
+            .unwrap();
+        worker.run_until_idle().unwrap();
+        worker
+            .execute(
+                "check.js",
+                "assert(order.join() === 'now,fast,slow', order.join())",
+            )
+            .unwrap();
+    }
 
-    (delay_task, TimerCancel(Some(tx)))
+    #[test]
+    fn js_cleared_timers_do_not_fire() {
+        let mut worker = Worker::new(RuntimeConfig::default());
+        worker
+            .execute(
+                "timers.js",
+                "var ticks = 0, fired = false;
+                 const interval = setInterval(() => {
+                     ticks += 1;
+                     if (ticks === 3) clearInterval(interval);
+                 }, 5);
+                 clearTimeout(setTimeout(() => { fired = true; }, 10));",
+            )
+            .unwrap();
+        // Ends only because the interval was cleared
+        worker.run_until_idle().unwrap();
+        worker
+            .execute(
+                "check.js",
+                "assert(ticks === 3, `ticks: ${ticks}`); assert(!fired, 'cleared timeout fired')",
+            )
+            .unwrap();
+    }
 }
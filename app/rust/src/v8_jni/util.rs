@@ -13,6 +13,7 @@ use v8::types::Managed;
 use crate::dex;
 use crate::dex::{unwrap, unwrap_js};
 use crate::v8::types::{Handle, JsValue};
+use crate::v8::Scope;
 use crate::v8_jni::{_rust_get_string, attach_current_thread, attach_current_thread_as_daemon};
 
 static INTEGER_CLASS: &str = "java/lang/Integer";
@@ -220,11 +221,18 @@ pub unsafe extern "C" fn instance_call_args(
     argc: u32,
     info: &CallbackInfo,
 ) {
-    info.set_return_value(internal_instance_call(instance_ptr, name, args, argc));
+    info.set_return_value(internal_instance_call(
+        &info.scope(),
+        instance_ptr,
+        name,
+        args,
+        argc,
+    ));
 }
 
 #[no_mangle]
 unsafe fn internal_instance_call<'a>(
+    scope: &Scope<'a>,
     instance_ptr: jlong,
     name: jlong,
     args: *const value_t,
@@ -286,7 +294,7 @@ unsafe fn internal_instance_call<'a>(
 
         if has_error {
             dex::throw_js_exception(&env, internal).unwrap();
-            return v8::null().upcast();
+            return v8::null(scope).upcast();
         }
 
         return match sig {
@@ -301,7 +309,7 @@ unsafe fn internal_instance_call<'a>(
                         &[internal],
                     ),
                 );
-                v8::new_number(unwrap(&env, value.i())).upcast()
+                v8::new_number(scope, unwrap(&env, value.i())).upcast()
             }
             1u8 => {
                 let value = unwrap(
@@ -314,7 +322,7 @@ unsafe fn internal_instance_call<'a>(
                         &[internal],
                     ),
                 );
-                v8::new_number(cast::f64(unwrap(&env, value.j()))).upcast()
+                v8::new_number(scope, cast::f64(unwrap(&env, value.j()))).upcast()
             }
             2u8 => {
                 let value = unwrap(
@@ -327,11 +335,11 @@ unsafe fn internal_instance_call<'a>(
                         &[internal],
                     ),
                 );
-                v8::new_number(unwrap(&env, value.d())).upcast()
+                v8::new_number(scope, unwrap(&env, value.d())).upcast()
             }
-            _ => v8::null().upcast(),
+            _ => v8::null(scope).upcast(),
         };
     }
 
-    v8::null().upcast()
+    v8::null(scope).upcast()
 }
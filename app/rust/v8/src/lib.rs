@@ -8,41 +8,68 @@ mod sys {
     pub mod macros;
     pub use macros::*;
 
+    pub mod error;
+
+    pub mod class;
+
+    pub mod convert;
+
+    pub mod de;
+
     pub mod fun;
 
+    pub mod global;
+
+    pub mod scope;
+
+    pub mod ser;
+
     pub mod types;
+
+    pub mod typed_array;
 }
 
 mod macros;
 pub use macros::*;
 pub use sys::*;
 
+pub use sys::de::from_value;
+pub use sys::ser::to_value;
+
+pub use sys::class::ClassBuilder;
+pub use sys::global::{Global, Weak};
+pub use sys::scope::{EscapableScope, Scope};
+
 use crate::sys::types::{
-    Handle, JsArray, JsArrayBuffer, JsNull, JsNumber, JsObject, JsString, JsValue,
+    Handle, JsArray, JsArrayBuffer, JsBoolean, JsNull, JsNumber, JsObject, JsString, JsValue,
 };
 
-pub fn new_string<'a>(data: &str) -> Handle<'a, JsString> {
-    JsString::new(data)
+pub fn new_string<'a>(scope: &Scope<'a>, data: &str) -> Handle<'a, JsString> {
+    JsString::new(scope, data)
+}
+
+pub fn new_number<'a, T: Into<f64>>(scope: &Scope<'a>, data: T) -> Handle<'a, JsNumber> {
+    JsNumber::new(scope, data)
 }
 
-pub fn new_number<'a, T: Into<f64>>(data: T) -> Handle<'a, JsNumber> {
-    JsNumber::new(data)
+pub fn new_boolean<'a>(scope: &Scope<'a>, data: bool) -> Handle<'a, JsBoolean> {
+    JsBoolean::new(scope, data)
 }
 
-pub fn empty_array<'a>() -> Handle<'a, JsArray> {
-    JsArray::empty_array()
+pub fn empty_array<'a>(scope: &Scope<'a>) -> Handle<'a, JsArray> {
+    JsArray::empty_array(scope)
 }
 
-pub fn empty_object<'a>() -> Handle<'a, JsObject> {
-    JsObject::empty_object()
+pub fn empty_object<'a>(scope: &Scope<'a>) -> Handle<'a, JsObject> {
+    JsObject::empty_object(scope)
 }
 
-pub fn new_array_buffer<'a>(data: &[u8]) -> Handle<'a, JsArrayBuffer> {
-    JsArrayBuffer::new(data)
+pub fn new_array_buffer<'a>(scope: &Scope<'a>, data: &[u8]) -> Handle<'a, JsArrayBuffer> {
+    JsArrayBuffer::new(scope, data)
 }
 
-pub fn null<'a>() -> Handle<'a, JsNull> {
-    JsNull::new()
+pub fn null<'a>(scope: &Scope<'a>) -> Handle<'a, JsNull> {
+    JsNull::new(scope)
 }
 
 pub fn empty_args<'a>() -> Vec<Handle<'a, JsValue>> {
@@ -1,9 +1,10 @@
 #[macro_export]
 macro_rules! js_object {
-    ( $($key:expr => $value:expr), *) => {{
-        let object = $crate::empty_object();
+    ( $scope:expr, $($key:expr => $value:expr), *) => {{
+        let scope = $scope;
+        let object = $crate::empty_object(scope);
         $(
-            object.set_from_raw($key, $value);
+            object.set_from_raw(scope, $key, $value);
         )*
         object
     }};
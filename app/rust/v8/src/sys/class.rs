@@ -0,0 +1,238 @@
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+
+use crate::sys::convert::{FromArg, IntoReturn};
+use crate::sys::error::TypeError;
+use crate::sys::fun::{CallbackInfo, FunctionCallback};
+use crate::sys::scope::Scope;
+use crate::sys::types::{Handle, JsFunction, JsObject, JsValue, Local, Managed};
+
+extern "C" {
+    fn new_class_template(
+        out: &mut Local,
+        name: *const u8,
+        len: u32,
+        constructor: FunctionCallback,
+        data: *mut c_void,
+    );
+    fn class_template_set_method(
+        template: Local,
+        name: *const u8,
+        len: u32,
+        cb: FunctionCallback,
+        data: *mut c_void,
+    );
+    fn class_template_set_accessor(
+        template: Local,
+        name: *const u8,
+        len: u32,
+        getter: FunctionCallback,
+        getter_data: *mut c_void,
+        setter: Option<FunctionCallback>,
+        setter_data: *mut c_void,
+    );
+    fn class_template_get_function(out: &mut Local, template: Local);
+    fn object_wrap(object: Local, value: *mut c_void, free_cb: extern "C" fn(*mut c_void));
+    fn object_unwrap(object: Local) -> *mut c_void;
+}
+
+/// The native value of an instance, behind its internal field.
+#[repr(C)]
+struct Wrapped<T> {
+    // Checked before the value is borrowed
+    type_id: TypeId,
+    value: RefCell<T>,
+}
+
+extern "C" fn free_wrapped<T>(value: *mut c_void) {
+    drop(unsafe { Box::from_raw(value as *mut Wrapped<T>) });
+}
+
+/// Defines a JavaScript class whose instances wrap a Rust `T`.
+///
+/// The constructor creates the value from the arguments of `new`, it is dropped once
+/// V8 collects the instance. Methods and accessors are plain functions, they can't
+/// capture state. Calling them on another receiver throws a `TypeError`.
+///
+/// ```ignore
+/// let counter = ClassBuilder::new(scope, "Counter", |_| Ok::<_, String>(0u32))
+///     .method("increment", |count, _| *count += 1)
+///     .getter("count", |count| *count)
+///     .build();
+/// ```
+pub struct ClassBuilder<'s, 'a, T> {
+    template: Local,
+    marker: PhantomData<(&'s Scope<'a>, T)>,
+}
+
+impl<'s, 'a, T: 'static> ClassBuilder<'s, 'a, T> {
+    /// A class called `name`, an `Err` from `constructor` is thrown as an `Error`.
+    pub fn new<E: Display>(
+        _scope: &'s Scope<'a>,
+        name: &str,
+        constructor: fn(&CallbackInfo) -> Result<T, E>,
+    ) -> Self {
+        unsafe {
+            let mut template: Local = mem::zeroed();
+            new_class_template(
+                &mut template,
+                name.as_ptr(),
+                name.len() as u32,
+                construct::<T, E>,
+                constructor as *mut c_void,
+            );
+            ClassBuilder {
+                template,
+                marker: PhantomData,
+            }
+        }
+    }
+
+    /// A method of the prototype, the returned value is converted by `IntoReturn`.
+    pub fn method<R: IntoReturn>(self, name: &str, f: fn(&mut T, &CallbackInfo) -> R) -> Self {
+        unsafe {
+            class_template_set_method(
+                self.template,
+                name.as_ptr(),
+                name.len() as u32,
+                call_method::<T, R>,
+                f as *mut c_void,
+            )
+        };
+        self
+    }
+
+    /// A read-only property.
+    pub fn getter<R: IntoReturn>(self, name: &str, get: fn(&T) -> R) -> Self {
+        unsafe {
+            class_template_set_accessor(
+                self.template,
+                name.as_ptr(),
+                name.len() as u32,
+                call_getter::<T, R>,
+                get as *mut c_void,
+                None,
+                std::ptr::null_mut(),
+            )
+        };
+        self
+    }
+
+    /// A property which can be assigned, the new value is read by `FromArg`.
+    pub fn accessor<R, V>(self, name: &str, get: fn(&T) -> R, set: fn(&mut T, V)) -> Self
+    where
+        R: IntoReturn,
+        V: for<'b> FromArg<'b>,
+    {
+        unsafe {
+            class_template_set_accessor(
+                self.template,
+                name.as_ptr(),
+                name.len() as u32,
+                call_getter::<T, R>,
+                get as *mut c_void,
+                Some(call_setter::<T, V>),
+                set as *mut c_void,
+            )
+        };
+        self
+    }
+
+    /// The constructor, to be set on a global or a module.
+    pub fn build(self) -> Handle<'a, JsFunction> {
+        unsafe {
+            let mut local: Local = mem::zeroed();
+            class_template_get_function(&mut local, self.template);
+            Handle::new_internal(JsFunction::from_raw(local))
+        }
+    }
+}
+
+/// Runs `f` with the value wrapped by `object`, fails if it is not an instance of a
+/// class of `T`.
+///
+/// Panics if the value is already borrowed, like by a method running `f`.
+pub fn with_instance<T: 'static, R, F>(object: Handle<JsObject>, f: F) -> Result<R, TypeError>
+where
+    F: FnOnce(&mut T) -> R,
+{
+    let wrapped = unwrap::<T>(object).ok_or_else(|| TypeError::new("instance", "object"))?;
+    let mut value = wrapped.value.borrow_mut();
+    Ok(f(&mut value))
+}
+
+fn unwrap<'o, T: 'static>(object: Handle<'o, JsObject>) -> Option<&'o Wrapped<T>> {
+    unsafe {
+        // Null unless the object is an instance of a `ClassBuilder` class, whose
+        // value is a `Wrapped` with the type id as its first field
+        let wrapped = object_unwrap(object.to_raw()) as *const Wrapped<T>;
+        if wrapped.is_null() || *(wrapped as *const TypeId) != TypeId::of::<T>() {
+            return None;
+        }
+        Some(&*wrapped)
+    }
+}
+
+/// Borrows the value of `this`, throws if it is already borrowed.
+fn with_this<T: 'static, F: FnOnce(&mut T)>(info: &CallbackInfo, f: F) {
+    // The signature of the member guarantees the receiver is an instance
+    let wrapped = match unwrap::<T>(info.this()) {
+        Some(wrapped) => wrapped,
+        None => return info.throw_type_error("Illegal invocation"),
+    };
+    match wrapped.value.try_borrow_mut() {
+        Ok(mut value) => f(&mut value),
+        Err(_) => info.throw_error("Object is already in use by a native call"),
+    }
+}
+
+extern "C" fn construct<T: 'static, E: Display>(info: &CallbackInfo) {
+    if !info.is_construct_call() {
+        return info.throw_type_error("Class constructor cannot be invoked without 'new'");
+    }
+    let constructor: fn(&CallbackInfo) -> Result<T, E> = unsafe { mem::transmute(info.data()) };
+    info.catch_panic(|| match constructor(info) {
+        Ok(value) => {
+            let wrapped = Box::new(Wrapped {
+                type_id: TypeId::of::<T>(),
+                value: RefCell::new(value),
+            });
+            unsafe {
+                object_wrap(
+                    info.this().to_raw(),
+                    Box::into_raw(wrapped) as *mut c_void,
+                    free_wrapped::<T>,
+                )
+            };
+        }
+        Err(err) => info.throw_error(&err.to_string()),
+    });
+}
+
+extern "C" fn call_method<T: 'static, R: IntoReturn>(info: &CallbackInfo) {
+    let f: fn(&mut T, &CallbackInfo) -> R = unsafe { mem::transmute(info.data()) };
+    info.catch_panic(|| with_this(info, |value: &mut T| f(value, info).set_return(info)));
+}
+
+extern "C" fn call_getter<T: 'static, R: IntoReturn>(info: &CallbackInfo) {
+    let get: fn(&T) -> R = unsafe { mem::transmute(info.data()) };
+    info.catch_panic(|| with_this(info, |value: &mut T| get(value).set_return(info)));
+}
+
+extern "C" fn call_setter<T: 'static, V: for<'b> FromArg<'b>>(info: &CallbackInfo) {
+    let set: fn(&mut T, V) = unsafe { mem::transmute(info.data()) };
+    info.catch_panic(|| {
+        let scope = info.scope();
+        let arg = info
+            .get::<JsValue>(0)
+            .and_then(|value| V::from_arg(&scope, value));
+        match arg {
+            Ok(arg) => with_this(info, |value: &mut T| set(value, arg)),
+            Err(err) => info.throw_type_error(&err.to_string()),
+        }
+    });
+}
@@ -0,0 +1,146 @@
+use std::fmt::Display;
+
+use crate::sys::error::TypeError;
+use crate::sys::fun::CallbackInfo;
+use crate::sys::scope::Scope;
+use crate::sys::types::{Handle, JsBoolean, JsNull, JsNumber, JsString, JsValue, Value};
+
+/// A Rust value a `#[v8_fn]` function takes as argument.
+pub trait FromArg<'a>: Sized {
+    /// Whether the argument may be left out, it is read from `undefined` then.
+    const OPTIONAL: bool = false;
+
+    fn from_arg(scope: &Scope<'a>, value: Handle<'a, JsValue>) -> Result<Self, TypeError>;
+}
+
+impl<'a> FromArg<'a> for f64 {
+    fn from_arg(_scope: &Scope<'a>, value: Handle<'a, JsValue>) -> Result<Self, TypeError> {
+        Ok(value.downcast::<JsNumber>()?.value())
+    }
+}
+
+impl<'a> FromArg<'a> for f32 {
+    fn from_arg(scope: &Scope<'a>, value: Handle<'a, JsValue>) -> Result<Self, TypeError> {
+        f64::from_arg(scope, value).map(|n| n as f32)
+    }
+}
+
+macro_rules! integer_arg {
+    ($($ty:ty: $bits:expr),*) => {
+        $(
+            impl<'a> FromArg<'a> for $ty {
+                fn from_arg(
+                    scope: &Scope<'a>,
+                    value: Handle<'a, JsValue>,
+                ) -> Result<Self, TypeError> {
+                    let n = f64::from_arg(scope, value)?;
+                    // MAX rounds up to 2^bits as f64, so compare against that bound
+                    if n.fract() != 0.0 || n < <$ty>::MIN as f64 || n >= 2f64.powi($bits) {
+                        return Err(TypeError::new(stringify!($ty), "number"));
+                    }
+                    Ok(n as $ty)
+                }
+            }
+        )*
+    };
+}
+
+integer_arg!(i32: 31, u32: 32, i64: 63, u64: 64);
+
+impl<'a> FromArg<'a> for bool {
+    fn from_arg(_scope: &Scope<'a>, value: Handle<'a, JsValue>) -> Result<Self, TypeError> {
+        Ok(value.downcast::<JsBoolean>()?.value())
+    }
+}
+
+impl<'a> FromArg<'a> for String {
+    fn from_arg(_scope: &Scope<'a>, value: Handle<'a, JsValue>) -> Result<Self, TypeError> {
+        Ok(value.downcast::<JsString>()?.value())
+    }
+}
+
+impl<'a, T: Value> FromArg<'a> for Handle<'a, T> {
+    fn from_arg(_scope: &Scope<'a>, value: Handle<'a, JsValue>) -> Result<Self, TypeError> {
+        value.downcast()
+    }
+}
+
+/// `None` for `undefined` and `null`.
+impl<'a, T: FromArg<'a>> FromArg<'a> for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn from_arg(scope: &Scope<'a>, value: Handle<'a, JsValue>) -> Result<Self, TypeError> {
+        if value.is_undefined() || value.is_null() {
+            Ok(None)
+        } else {
+            T::from_arg(scope, value).map(Some)
+        }
+    }
+}
+
+/// A Rust value a `#[v8_fn]` function returns to JavaScript.
+pub trait IntoReturn {
+    fn set_return(self, info: &CallbackInfo);
+}
+
+impl IntoReturn for () {
+    fn set_return(self, _info: &CallbackInfo) {}
+}
+
+macro_rules! number_return {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoReturn for $ty {
+                fn set_return(self, info: &CallbackInfo) {
+                    info.set_return_value(JsNumber::new(&info.scope(), self as f64));
+                }
+            }
+        )*
+    };
+}
+
+number_return!(f64, f32, i32, u32, i64, u64);
+
+impl IntoReturn for bool {
+    fn set_return(self, info: &CallbackInfo) {
+        info.set_return_value(JsBoolean::new(&info.scope(), self));
+    }
+}
+
+impl IntoReturn for String {
+    fn set_return(self, info: &CallbackInfo) {
+        self.as_str().set_return(info)
+    }
+}
+
+impl IntoReturn for &str {
+    fn set_return(self, info: &CallbackInfo) {
+        info.set_return_value(JsString::new(&info.scope(), self));
+    }
+}
+
+impl<'a, T: Value> IntoReturn for Handle<'a, T> {
+    fn set_return(self, info: &CallbackInfo) {
+        info.set_return_value(self);
+    }
+}
+
+/// `None` returns `null`.
+impl<T: IntoReturn> IntoReturn for Option<T> {
+    fn set_return(self, info: &CallbackInfo) {
+        match self {
+            Some(value) => value.set_return(info),
+            None => info.set_return_value(JsNull::new(&info.scope())),
+        }
+    }
+}
+
+/// An error is thrown as an `Error` with its message.
+impl<T: IntoReturn, E: Display> IntoReturn for Result<T, E> {
+    fn set_return(self, info: &CallbackInfo) {
+        match self {
+            Ok(value) => value.set_return(info),
+            Err(err) => info.throw_error(&err.to_string()),
+        }
+    }
+}
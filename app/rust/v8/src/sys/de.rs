@@ -0,0 +1,317 @@
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::sys::error::{ConvertError, MAX_DEPTH};
+use crate::sys::scope::Scope;
+use crate::sys::types::{
+    Handle, JsArray, JsArrayBuffer, JsBoolean, JsNumber, JsObject, JsString, JsValue, Object,
+};
+
+/// Reads a Rust value out of a JavaScript value.
+///
+/// `undefined` is read like `null` and an `ArrayBuffer` like bytes. Functions,
+/// promises and other exotic values can't be read, nor values nested deeper than 128
+/// levels, like cyclic objects.
+pub fn from_value<'a, T: DeserializeOwned>(
+    scope: &Scope<'a>,
+    value: Handle<'a, JsValue>,
+) -> Result<T, ConvertError> {
+    T::deserialize(Deserializer::new(scope, value, 0))
+}
+
+pub struct Deserializer<'s, 'a> {
+    scope: &'s Scope<'a>,
+    value: Handle<'a, JsValue>,
+    // Arrays and objects around the value
+    depth: usize,
+}
+
+impl<'s, 'a> Deserializer<'s, 'a> {
+    fn new(scope: &'s Scope<'a>, value: Handle<'a, JsValue>, depth: usize) -> Self {
+        Deserializer {
+            scope,
+            value,
+            depth,
+        }
+    }
+
+    /// The depth of the values inside this one.
+    fn enter(&self) -> Result<usize, ConvertError> {
+        if self.depth >= MAX_DEPTH {
+            Err(ConvertError::too_deep())
+        } else {
+            Ok(self.depth + 1)
+        }
+    }
+}
+
+/// Numbers holding an integer are read as integers, so they fit integer fields.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+impl<'de, 's, 'a> de::Deserializer<'de> for Deserializer<'s, 'a> {
+    type Error = ConvertError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let value = self.value;
+        if value.is_null() || value.is_undefined() {
+            visitor.visit_unit()
+        } else if value.is_boolean() {
+            visitor.visit_bool(value.downcast::<JsBoolean>()?.value())
+        } else if value.is_number() {
+            let n = value.downcast::<JsNumber>()?.value();
+            if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER {
+                if n >= 0.0 {
+                    visitor.visit_u64(n as u64)
+                } else {
+                    visitor.visit_i64(n as i64)
+                }
+            } else {
+                visitor.visit_f64(n)
+            }
+        } else if value.is_string() {
+            visitor.visit_string(value.downcast::<JsString>()?.value())
+        } else if value.is_array() {
+            let depth = self.enter()?;
+            let array = value.downcast::<JsArray>()?;
+            let len = array.len();
+            let result = visitor.visit_seq(ArrayAccess {
+                scope: self.scope,
+                array,
+                index: 0,
+                len,
+                depth,
+            })?;
+            Ok(result)
+        } else if value.is_array_buffer() {
+            visitor.visit_bytes(value.downcast::<JsArrayBuffer>()?.as_slice())
+        } else if value.is_function() || value.is_promise() {
+            Err(ConvertError::new(format!(
+                "can't read a {} into a Rust value",
+                value.type_name()
+            )))
+        } else if value.is_object() {
+            let depth = self.enter()?;
+            let object = value.downcast::<JsObject>()?;
+            let keys = object.get_own_property_names(self.scope);
+            let len = keys.len();
+            visitor.visit_map(ObjectAccess {
+                scope: self.scope,
+                object,
+                keys,
+                index: 0,
+                len,
+                key: None,
+                depth,
+            })
+        } else {
+            Err(ConvertError::new(format!(
+                "can't read a {} into a Rust value",
+                value.type_name()
+            )))
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.value.is_null() || self.value.is_undefined() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Unit variants are strings, other variants objects with a single key.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self.value;
+        if value.is_string() {
+            let variant = value.downcast::<JsString>()?.value();
+            return visitor.visit_enum(variant.into_deserializer());
+        }
+        if !value.is_object() || value.is_array() {
+            return Err(ConvertError::new(format!(
+                "expected a string or an object for an enum, found {}",
+                value.type_name()
+            )));
+        }
+        let depth = self.enter()?;
+        let object = value.downcast::<JsObject>()?;
+        let keys = object.get_own_property_names(self.scope);
+        if keys.len() != 1 {
+            return Err(ConvertError::new(
+                "expected an object with a single key for an enum",
+            ));
+        }
+        let variant = keys.get::<JsString, _>(self.scope, 0)?.value();
+        let content = object.get::<JsValue, _>(self.scope, variant.as_str())?;
+        visitor.visit_enum(VariantAccess {
+            scope: self.scope,
+            variant,
+            content,
+            depth,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct ArrayAccess<'s, 'a> {
+    scope: &'s Scope<'a>,
+    array: Handle<'a, JsArray>,
+    index: u32,
+    len: u32,
+    depth: usize,
+}
+
+impl<'de, 's, 'a> de::SeqAccess<'de> for ArrayAccess<'s, 'a> {
+    type Error = ConvertError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        if self.index >= self.len {
+            return Ok(None);
+        }
+        let index = self.index;
+        self.index += 1;
+        let value = self.array.get::<JsValue, _>(self.scope, index)?;
+        seed.deserialize(Deserializer::new(self.scope, value, self.depth))
+            .map(Some)
+            .map_err(|e| e.at_index(index))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.len - self.index) as usize)
+    }
+}
+
+struct ObjectAccess<'s, 'a> {
+    scope: &'s Scope<'a>,
+    object: Handle<'a, JsObject>,
+    keys: Handle<'a, JsArray>,
+    index: u32,
+    len: u32,
+    // The key of the value read next
+    key: Option<String>,
+    depth: usize,
+}
+
+impl<'de, 's, 'a> de::MapAccess<'de> for ObjectAccess<'s, 'a> {
+    type Error = ConvertError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        if self.index >= self.len {
+            return Ok(None);
+        }
+        let key = self
+            .keys
+            .get::<JsString, _>(self.scope, self.index)?
+            .value();
+        self.index += 1;
+        let result = seed.deserialize(key.as_str().into_deserializer());
+        self.key = Some(key);
+        result.map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ConvertError::new("next_value_seed called before next_key_seed"))?;
+        let value = self.object.get::<JsValue, _>(self.scope, key.as_str())?;
+        seed.deserialize(Deserializer::new(self.scope, value, self.depth))
+            .map_err(|e| e.at_key(key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.len - self.index) as usize)
+    }
+}
+
+struct VariantAccess<'s, 'a> {
+    scope: &'s Scope<'a>,
+    variant: String,
+    content: Handle<'a, JsValue>,
+    depth: usize,
+}
+
+impl<'de, 's, 'a> de::EnumAccess<'de> for VariantAccess<'s, 'a> {
+    type Error = ConvertError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), Self::Error> {
+        let variant: de::value::StrDeserializer<ConvertError> =
+            self.variant.as_str().into_deserializer();
+        let variant = seed.deserialize(variant)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 's, 'a> de::VariantAccess<'de> for VariantAccess<'s, 'a> {
+    type Error = ConvertError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        de::Deserialize::deserialize(Deserializer::new(self.scope, self.content, self.depth))
+            .map_err(|e: ConvertError| e.at_key(self.variant))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        let variant = self.variant;
+        seed.deserialize(Deserializer::new(self.scope, self.content, self.depth))
+            .map_err(|e| e.at_key(variant))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let variant = self.variant;
+        de::Deserializer::deserialize_seq(
+            Deserializer::new(self.scope, self.content, self.depth),
+            visitor,
+        )
+        .map_err(|e| e.at_key(variant))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let variant = self.variant;
+        de::Deserializer::deserialize_map(
+            Deserializer::new(self.scope, self.content, self.depth),
+            visitor,
+        )
+        .map_err(|e| e.at_key(variant))
+    }
+}
@@ -0,0 +1,151 @@
+use std::error::Error;
+use std::fmt;
+
+/// A value doesn't have the JavaScript type it was expected to have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl TypeError {
+    pub fn new(expected: &'static str, found: &'static str) -> Self {
+        TypeError { expected, found }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl Error for TypeError {}
+
+/// Calling a JavaScript function, or a getter, failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallError {
+    /// The function threw, the exception is left pending for the caller of the native
+    /// code.
+    Threw,
+    /// The function returned a value of another type.
+    Type(TypeError),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CallError::Threw => write!(f, "function threw an exception"),
+            CallError::Type(err) => write!(f, "function returned a bad value: {}", err),
+        }
+    }
+}
+
+impl Error for CallError {}
+
+impl From<TypeError> for CallError {
+    fn from(err: TypeError) -> Self {
+        CallError::Type(err)
+    }
+}
+
+/// A step into a JavaScript value, for error paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(u32),
+}
+
+/// Levels of nesting `to_value` and `from_value` go through, deeper values are most
+/// likely cyclic.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+/// Converting between a Rust value and a JavaScript value failed.
+///
+/// `path` leads from the converted value to the property which failed, like
+/// `favorites[2].name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertError {
+    pub message: String,
+    pub path: Vec<PathSegment>,
+}
+
+impl ConvertError {
+    pub fn new<T: Into<String>>(message: T) -> Self {
+        ConvertError {
+            message: message.into(),
+            path: Vec::new(),
+        }
+    }
+
+    /// The value is nested deeper than `MAX_DEPTH`.
+    pub(crate) fn too_deep() -> Self {
+        ConvertError::new(format!(
+            "value nested deeper than {} levels, it may be cyclic",
+            MAX_DEPTH
+        ))
+    }
+
+    /// Prefixes the path with `key`, while the error bubbles up.
+    pub(crate) fn at_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.path.insert(0, PathSegment::Key(key.into()));
+        self
+    }
+
+    /// Prefixes the path with `index`, while the error bubbles up.
+    pub(crate) fn at_index(mut self, index: u32) -> Self {
+        self.path.insert(0, PathSegment::Index(index));
+        self
+    }
+
+    pub fn path_string(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.path {
+            match segment {
+                PathSegment::Key(key) if path.is_empty() => path.push_str(key),
+                PathSegment::Key(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{} at {}", self.message, self.path_string())
+        }
+    }
+}
+
+impl Error for ConvertError {}
+
+impl From<TypeError> for ConvertError {
+    fn from(err: TypeError) -> Self {
+        ConvertError::new(err.to_string())
+    }
+}
+
+impl From<CallError> for ConvertError {
+    fn from(err: CallError) -> Self {
+        ConvertError::new(err.to_string())
+    }
+}
+
+impl serde::ser::Error for ConvertError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ConvertError::new(msg.to_string())
+    }
+}
+
+impl serde::de::Error for ConvertError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ConvertError::new(msg.to_string())
+    }
+}
@@ -1,5 +1,8 @@
-use crate::sys::types::{Handle, Local, Value};
+use crate::sys::error::TypeError;
+use crate::sys::scope::Scope;
+use crate::sys::types::{Handle, JsObject, JsValue, Local, Managed, Value};
 use libc::c_void;
+use std::panic::{self, AssertUnwindSafe};
 
 pub type FunctionCallback = extern "C" fn(args: &CallbackInfo);
 pub type FunctionCallbackInfo = c_void;
@@ -7,6 +10,12 @@ pub type FunctionCallbackInfo = c_void;
 extern "C" {
     fn set_return_value(info: &FunctionCallbackInfo, value: Local);
     fn callback_info_get(info: &FunctionCallbackInfo, index: u32, local: &mut Local);
+    fn callback_info_length(info: &FunctionCallbackInfo) -> u32;
+    fn callback_info_data(info: &FunctionCallbackInfo) -> *mut c_void;
+    fn callback_info_this(info: &FunctionCallbackInfo, out: &mut Local);
+    fn callback_info_is_construct_call(info: &FunctionCallbackInfo) -> bool;
+    fn throw_error(data: *const u8, len: u32);
+    fn throw_type_error(data: *const u8, len: u32);
 }
 
 #[repr(C)]
@@ -23,11 +32,68 @@ impl CallbackInfo {
             self.set_return_value(value);
         }
     }
-    pub fn get<'a, T: Value>(&self, index: u32) -> Handle<'a, T> {
+    /// The scope V8 opens for the call, it closes when the callback returns.
+    pub fn scope(&self) -> Scope<'_> {
+        Scope::new_internal()
+    }
+
+    /// The number of arguments passed.
+    pub fn len(&self) -> u32 {
+        unsafe { callback_info_length(&self.info) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The receiver of the call, the new instance for a constructor.
+    pub fn this(&self) -> Handle<'_, JsObject> {
+        unsafe {
+            let mut local: Local = std::mem::zeroed();
+            callback_info_this(&self.info, &mut local);
+            Handle::new_internal(JsObject::from_raw(local))
+        }
+    }
+
+    /// Whether the function was called with `new`.
+    pub fn is_construct_call(&self) -> bool {
+        unsafe { callback_info_is_construct_call(&self.info) }
+    }
+
+    /// Throws an `Error` with `message` once the callback returns.
+    pub fn throw_error(&self, message: &str) {
+        unsafe { throw_error(message.as_ptr(), message.len() as u32) }
+    }
+
+    /// Throws a `TypeError` with `message` once the callback returns.
+    pub fn throw_type_error(&self, message: &str) {
+        unsafe { throw_type_error(message.as_ptr(), message.len() as u32) }
+    }
+
+    /// Runs the body of a callback. Panics can't unwind through V8, they are thrown
+    /// as an `Error` instead.
+    pub fn catch_panic<F: FnOnce()>(&self, f: F) {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            self.throw_error(&format!("Native function panicked: {}", message));
+        }
+    }
+
+    /// The data of a function created by `JsFunction::from_closure`.
+    pub(crate) fn data(&self) -> *mut c_void {
+        unsafe { callback_info_data(&self.info) }
+    }
+
+    /// The argument at `index`, `undefined` past the last one. Fails if it is not a `T`.
+    pub fn get<T: Value>(&self, index: u32) -> Result<Handle<'_, T>, TypeError> {
         unsafe {
             let mut local: Local = std::mem::zeroed();
             callback_info_get(&self.info, index, &mut local);
-            Handle::new_internal(T::from_raw(local))
+            Handle::new_internal(JsValue::from_raw(local)).downcast()
         }
     }
 }
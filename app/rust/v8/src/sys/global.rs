@@ -0,0 +1,298 @@
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::sys::scope::Scope;
+use crate::sys::types::{Handle, Local, Value};
+
+extern "C" {
+    fn isolate_get_data(slot: u32) -> *mut c_void;
+    fn isolate_set_data(slot: u32, data: *mut c_void);
+    fn global_new(value: Local) -> *mut RawGlobal;
+    fn global_get(out: &mut Local, global: *mut RawGlobal);
+    fn global_delete(global: *mut RawGlobal);
+    fn global_set_weak(global: *mut RawGlobal, data: *mut c_void, cb: extern "C" fn(*mut c_void));
+    fn global_delete_weak(global: *mut RawGlobal);
+    fn isolate_collect_garbage();
+}
+
+/// Isolate data slot holding the release queue, slot 0 belongs to the embedder.
+const QUEUE_SLOT: u32 = 1;
+
+#[repr(C)]
+struct RawGlobal {
+    _unused: [u8; 0],
+}
+
+/// A value kept alive across scopes, until the `Global` is dropped.
+///
+/// It can be stored anywhere and dropped from any thread, the handle is released by
+/// the isolate thread at its next `release_pending`.
+pub struct Global<T: Value> {
+    raw: *mut RawGlobal,
+    queue: Arc<ReleaseQueue>,
+    marker: PhantomData<T>,
+}
+
+// The raw handle is only touched on the isolate thread
+unsafe impl<T: Value> Send for Global<T> {}
+unsafe impl<T: Value> Sync for Global<T> {}
+
+impl<T: Value> Global<T> {
+    pub fn new<'a>(_scope: &Scope<'a>, handle: Handle<'a, T>) -> Self {
+        Global {
+            raw: unsafe { global_new(handle.to_raw()) },
+            queue: ReleaseQueue::current(),
+            marker: PhantomData,
+        }
+    }
+
+    /// The value as a handle of `scope`, which must belong to the same isolate.
+    pub fn get<'a>(&self, _scope: &Scope<'a>) -> Handle<'a, T> {
+        self.queue.assert_current();
+        unsafe {
+            let mut local: Local = mem::zeroed();
+            global_get(&mut local, self.raw);
+            Handle::new_internal(T::from_raw(local))
+        }
+    }
+}
+
+impl<T: Value> Drop for Global<T> {
+    fn drop(&mut self) {
+        self.queue.push(Release {
+            raw: self.raw,
+            weak: None,
+        });
+    }
+}
+
+struct WeakState {
+    collected: AtomicBool,
+    finalizer: Mutex<Option<Box<dyn FnOnce() + Send>>>,
+}
+
+extern "C" fn weak_collected(data: *mut c_void) {
+    // Takes back the reference handed to V8 by `Weak::new`
+    let state = unsafe { Arc::from_raw(data as *const WeakState) };
+    state.collected.store(true, Ordering::SeqCst);
+    let finalizer = state.finalizer.lock().unwrap().take();
+    if let Some(finalizer) = finalizer {
+        // Panics can't unwind through the garbage collector
+        if panic::catch_unwind(AssertUnwindSafe(finalizer)).is_err() {
+            log::error!("Weak finalizer panicked");
+        }
+    }
+}
+
+/// A value which doesn't keep itself alive.
+///
+/// The finalizer runs on the isolate thread once V8 collected the value, it must not
+/// call into V8. Like `Global` it can be dropped from any thread.
+pub struct Weak<T: Value> {
+    raw: *mut RawGlobal,
+    state: Arc<WeakState>,
+    queue: Arc<ReleaseQueue>,
+    marker: PhantomData<T>,
+}
+
+unsafe impl<T: Value> Send for Weak<T> {}
+unsafe impl<T: Value> Sync for Weak<T> {}
+
+impl<T: Value> Weak<T> {
+    pub fn new<'a, F>(_scope: &Scope<'a>, handle: Handle<'a, T>, finalizer: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let state = Arc::new(WeakState {
+            collected: AtomicBool::new(false),
+            finalizer: Mutex::new(Some(Box::new(finalizer))),
+        });
+        let raw = unsafe {
+            let raw = global_new(handle.to_raw());
+            let data = Arc::into_raw(state.clone()) as *mut c_void;
+            global_set_weak(raw, data, weak_collected);
+            raw
+        };
+        Weak {
+            raw,
+            state,
+            queue: ReleaseQueue::current(),
+            marker: PhantomData,
+        }
+    }
+
+    /// The value, or `None` once it was collected.
+    pub fn upgrade<'a>(&self, _scope: &Scope<'a>) -> Option<Handle<'a, T>> {
+        self.queue.assert_current();
+        if self.is_collected() {
+            return None;
+        }
+        unsafe {
+            let mut local: Local = mem::zeroed();
+            global_get(&mut local, self.raw);
+            Some(Handle::new_internal(T::from_raw(local)))
+        }
+    }
+
+    pub fn is_collected(&self) -> bool {
+        self.state.collected.load(Ordering::SeqCst)
+    }
+}
+
+impl<T: Value> Drop for Weak<T> {
+    fn drop(&mut self) {
+        self.queue.push(Release {
+            raw: self.raw,
+            weak: Some(self.state.clone()),
+        });
+    }
+}
+
+/// A handle waiting to be released on the isolate thread.
+struct Release {
+    raw: *mut RawGlobal,
+    weak: Option<Arc<WeakState>>,
+}
+
+unsafe impl Send for Release {}
+
+impl Release {
+    /// Must run on the isolate thread, with the isolate locked.
+    unsafe fn run(self) {
+        match self.weak {
+            Some(ref state) if !state.collected.load(Ordering::SeqCst) => {
+                global_delete_weak(self.raw);
+                // V8 won't run `weak_collected` anymore, drop its reference
+                drop(Arc::from_raw(Arc::as_ptr(state)));
+            }
+            _ => global_delete(self.raw),
+        }
+    }
+}
+
+type Notifier = Arc<dyn Fn() + Send + Sync>;
+
+struct QueueState {
+    // False once the isolate is disposed
+    alive: bool,
+    pending: Vec<Release>,
+    notify: Option<Notifier>,
+}
+
+/// Handles dropped since the last `release_pending`, one queue per isolate.
+struct ReleaseQueue {
+    isolate_id: usize,
+    state: Mutex<QueueState>,
+}
+
+impl ReleaseQueue {
+    /// The queue of the current isolate, created on first use.
+    fn current() -> Arc<ReleaseQueue> {
+        unsafe {
+            let mut data = isolate_get_data(QUEUE_SLOT) as *const ReleaseQueue;
+            if data.is_null() {
+                let queue = Arc::new(ReleaseQueue {
+                    isolate_id: Self::current_id(),
+                    state: Mutex::new(QueueState {
+                        alive: true,
+                        pending: Vec::new(),
+                        notify: None,
+                    }),
+                });
+                // The slot owns one reference, until `dispose`
+                data = Arc::into_raw(queue);
+                isolate_set_data(QUEUE_SLOT, data as *mut c_void);
+            }
+            let queue = Arc::from_raw(data);
+            let current = queue.clone();
+            mem::forget(queue);
+            current
+        }
+    }
+
+    fn current_id() -> usize {
+        // The address of the slot's owner is unique per isolate
+        unsafe { isolate_get_data(0) as usize }
+    }
+
+    fn assert_current(&self) {
+        assert_eq!(
+            self.isolate_id,
+            Self::current_id(),
+            "handle used in another isolate"
+        );
+    }
+
+    fn push(&self, release: Release) {
+        let notify = {
+            let mut state = self.state.lock().unwrap();
+            if !state.alive {
+                // The isolate and its handles are gone already
+                mem::forget(release);
+                return;
+            }
+            state.pending.push(release);
+            state.notify.clone()
+        };
+        if let Some(notify) = notify {
+            notify();
+        }
+    }
+
+    fn drain(&self) -> Vec<Release> {
+        mem::take(&mut self.state.lock().unwrap().pending)
+    }
+}
+
+/// Releases the handles dropped since the last call, on the isolate of `scope`.
+///
+/// Embedders call it regularly, e.g. on every turn of their event loop.
+pub fn release_pending(_scope: &Scope) {
+    for release in ReleaseQueue::current().drain() {
+        unsafe { release.run() };
+    }
+}
+
+/// Calls `notify`, from any thread, when a handle of the isolate of `scope` is
+/// dropped, so the embedder can schedule a `release_pending`.
+pub fn set_release_notifier<F>(_scope: &Scope, notify: F)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    ReleaseQueue::current().state.lock().unwrap().notify = Some(Arc::new(notify));
+}
+
+/// Collects the unreachable values of the isolate of `scope`, the finalizers of `Weak`
+/// handles and native closures run before it returns. Values with a handle in an
+/// open scope are reachable.
+///
+/// Meant for tests, V8 collects garbage by itself.
+pub fn collect_garbage(_scope: &Scope) {
+    unsafe { isolate_collect_garbage() }
+}
+
+/// Releases pending handles before the isolate of `scope` is disposed. Handles
+/// dropped afterwards are ignored.
+pub fn dispose(_scope: &Scope) {
+    unsafe {
+        let data = isolate_get_data(QUEUE_SLOT) as *const ReleaseQueue;
+        if data.is_null() {
+            return;
+        }
+        isolate_set_data(QUEUE_SLOT, std::ptr::null_mut());
+        let queue = Arc::from_raw(data);
+        let pending = {
+            let mut state = queue.state.lock().unwrap();
+            state.alive = false;
+            state.notify = None;
+            mem::take(&mut state.pending)
+        };
+        for release in pending {
+            release.run();
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+use crate::sys::types::{Handle, Local, Managed};
+
+extern "C" {
+    fn isolate_entered() -> bool;
+    fn with_handle_scope(data: *mut c_void, cb: extern "C" fn(*mut c_void));
+    fn with_escapable_handle_scope(
+        data: *mut c_void,
+        cb: extern "C" fn(*mut c_void, *mut EscapableHandleScope),
+    );
+    fn escape_handle(out: &mut Local, scope: *mut EscapableHandleScope, value: Local);
+}
+
+#[repr(C)]
+struct EscapableHandleScope {
+    _unused: [u8; 0],
+}
+
+/// A V8 `HandleScope`.
+///
+/// Values are created through a `&Scope<'a>` and their handles live for `'a`, so they
+/// can't outlive the scope. The locals behind them are released when it closes.
+pub struct Scope<'a> {
+    // Bound to the isolate thread
+    phantom: PhantomData<(&'a (), *mut ())>,
+}
+
+thread_local! {
+    // Whether a scope opened by `Scope::with` is open on this thread
+    static ROOT_OPEN: Cell<bool> = const { Cell::new(false) };
+}
+
+impl<'a> Scope<'a> {
+    pub(crate) fn new_internal() -> Self {
+        Scope {
+            phantom: PhantomData,
+        }
+    }
+
+    /// Runs `f` in a new scope. Handles created in `f` can't be returned, locals are
+    /// released when it returns.
+    ///
+    /// This opens the outermost scope of the thread, scopes inside it are opened with
+    /// `nested` or `escapable`. Panics if no isolate is entered on the current thread
+    /// or if a scope is open already.
+    pub fn with<R, F>(f: F) -> R
+    where
+        F: for<'b> FnOnce(&mut Scope<'b>) -> R,
+    {
+        assert!(
+            unsafe { isolate_entered() },
+            "Scope::with called without an entered isolate"
+        );
+        assert!(
+            !ROOT_OPEN.with(|open| open.replace(true)),
+            "Scope::with called inside another scope, use Scope::nested"
+        );
+        let result = panic::catch_unwind(AssertUnwindSafe(|| Self::open(f)));
+        ROOT_OPEN.with(|open| open.set(false));
+        match result {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Runs `f` in a scope nested in this one. It is borrowed until `f` returns, so
+    /// new handles can't be created in the outer scope meanwhile:
+    ///
+    /// ```compile_fail
+    /// v8::Scope::with(|scope| {
+    ///     scope.nested(|_inner| {
+    ///         v8::new_number(scope, 1);
+    ///     });
+    /// });
+    /// ```
+    pub fn nested<R, F>(&mut self, f: F) -> R
+    where
+        F: for<'b> FnOnce(&mut Scope<'b>) -> R,
+    {
+        Self::open(f)
+    }
+
+    fn open<R, F>(f: F) -> R
+    where
+        F: for<'b> FnOnce(&mut Scope<'b>) -> R,
+    {
+        extern "C" fn run<R, F>(data: *mut c_void)
+        where
+            F: for<'b> FnOnce(&mut Scope<'b>) -> R,
+        {
+            let (f, result) =
+                unsafe { &mut *(data as *mut (Option<F>, Option<thread::Result<R>>)) };
+            let f = f.take().unwrap();
+            // Panics can't unwind through V8
+            *result = Some(panic::catch_unwind(AssertUnwindSafe(|| {
+                f(&mut Scope::new_internal())
+            })));
+        }
+
+        let mut state: (Option<F>, Option<thread::Result<R>>) = (Some(f), None);
+        unsafe { with_handle_scope(&mut state as *mut _ as *mut c_void, run::<R, F>) };
+        match state.1.unwrap() {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Runs `f` in a nested scope, one handle can be moved to this scope with
+    /// `EscapableScope::escape`. Like `nested`, this scope is borrowed until `f`
+    /// returns:
+    ///
+    /// ```compile_fail
+    /// v8::Scope::with(|scope| {
+    ///     scope.escapable(|_inner| {
+    ///         v8::new_number(scope, 1);
+    ///     });
+    /// });
+    /// ```
+    pub fn escapable<R, F>(&mut self, f: F) -> R
+    where
+        F: for<'b> FnOnce(&mut EscapableScope<'a, 'b>) -> R,
+    {
+        extern "C" fn run<'a, R, F>(data: *mut c_void, raw: *mut EscapableHandleScope)
+        where
+            F: for<'b> FnOnce(&mut EscapableScope<'a, 'b>) -> R,
+        {
+            let (f, result) =
+                unsafe { &mut *(data as *mut (Option<F>, Option<thread::Result<R>>)) };
+            let f = f.take().unwrap();
+            let mut scope = EscapableScope {
+                scope: Scope::new_internal(),
+                raw,
+                escaped: Cell::new(false),
+                parent: PhantomData,
+            };
+            *result = Some(panic::catch_unwind(AssertUnwindSafe(|| f(&mut scope))));
+        }
+
+        let mut state: (Option<F>, Option<thread::Result<R>>) = (Some(f), None);
+        unsafe {
+            with_escapable_handle_scope(&mut state as *mut _ as *mut c_void, run::<'a, R, F>)
+        };
+        match state.1.unwrap() {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+/// A scope nested in a `Scope<'a>` which can hand one of its handles back to it.
+pub struct EscapableScope<'a, 'b> {
+    scope: Scope<'b>,
+    raw: *mut EscapableHandleScope,
+    escaped: Cell<bool>,
+    parent: PhantomData<&'a ()>,
+}
+
+impl<'a, 'b> EscapableScope<'a, 'b> {
+    /// Moves `handle` to the parent scope.
+    ///
+    /// Panics when called twice, V8 only allows one escape per scope.
+    pub fn escape<T: Managed>(&self, handle: Handle<'b, T>) -> Handle<'a, T> {
+        assert!(
+            !self.escaped.replace(true),
+            "a scope can only escape one handle"
+        );
+        unsafe {
+            let mut local: Local = std::mem::zeroed();
+            escape_handle(&mut local, self.raw, handle.to_raw());
+            Handle::new_internal(T::from_raw(local))
+        }
+    }
+}
+
+impl<'a, 'b> Deref for EscapableScope<'a, 'b> {
+    type Target = Scope<'b>;
+
+    fn deref(&self) -> &Scope<'b> {
+        &self.scope
+    }
+}
+
+impl<'a, 'b> DerefMut for EscapableScope<'a, 'b> {
+    fn deref_mut(&mut self) -> &mut Scope<'b> {
+        &mut self.scope
+    }
+}
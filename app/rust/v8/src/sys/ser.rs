@@ -0,0 +1,569 @@
+use serde::ser::{self, Serialize};
+
+use crate::sys::error::{ConvertError, MAX_DEPTH};
+use crate::sys::scope::Scope;
+use crate::sys::types::{
+    Handle, JsArray, JsArrayBuffer, JsBoolean, JsNull, JsNumber, JsObject, JsString, JsValue,
+    Object,
+};
+
+/// Builds a JavaScript value out of `value`.
+///
+/// Structs and maps become objects, sequences and tuples arrays, bytes an
+/// `ArrayBuffer`, and `None` and `()` null. Enums are tagged like serde_json does.
+/// Values nested deeper than 128 levels fail.
+pub fn to_value<'a, T: Serialize + ?Sized>(
+    scope: &Scope<'a>,
+    value: &T,
+) -> Result<Handle<'a, JsValue>, ConvertError> {
+    value.serialize(Serializer::new(scope, 0))
+}
+
+pub struct Serializer<'s, 'a> {
+    scope: &'s Scope<'a>,
+    // Containers around the value
+    depth: usize,
+}
+
+impl<'s, 'a> Serializer<'s, 'a> {
+    fn new(scope: &'s Scope<'a>, depth: usize) -> Self {
+        Serializer { scope, depth }
+    }
+
+    /// The depth of the values inside this one.
+    fn enter(&self) -> Result<usize, ConvertError> {
+        if self.depth >= MAX_DEPTH {
+            Err(ConvertError::too_deep())
+        } else {
+            Ok(self.depth + 1)
+        }
+    }
+}
+
+/// Wraps `value` as `{ variant: value }`.
+fn tagged<'a>(scope: &Scope<'a>, variant: &str, value: Handle<'a, JsValue>) -> Handle<'a, JsValue> {
+    let object = JsObject::empty_object(scope);
+    object.set(variant, value);
+    object.upcast()
+}
+
+impl<'s, 'a> ser::Serializer for Serializer<'s, 'a> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = ConvertError;
+
+    type SerializeSeq = ArraySerializer<'s, 'a>;
+    type SerializeTuple = ArraySerializer<'s, 'a>;
+    type SerializeTupleStruct = ArraySerializer<'s, 'a>;
+    type SerializeTupleVariant = VariantSerializer<ArraySerializer<'s, 'a>>;
+    type SerializeMap = ObjectSerializer<'s, 'a>;
+    type SerializeStruct = ObjectSerializer<'s, 'a>;
+    type SerializeStructVariant = VariantSerializer<ObjectSerializer<'s, 'a>>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(JsBoolean::new(self.scope, v).upcast())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        // Precision is lost past 2^53, like JSON.parse does
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(JsNumber::new(self.scope, v).upcast())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(JsString::new(self.scope, v).upcast())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(JsArrayBuffer::new(self.scope, v).upcast())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        // Counted too, recursive types may nest options without containers
+        value.serialize(Serializer::new(self.scope, self.enter()?))
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(JsNull::new(self.scope).upcast())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(Serializer::new(self.scope, self.enter()?))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        let value = value
+            .serialize(Serializer::new(self.scope, self.enter()?))
+            .map_err(|e| e.at_key(variant))?;
+        Ok(tagged(self.scope, variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(ArraySerializer::new(
+            self.scope,
+            len.unwrap_or(0),
+            self.enter()?,
+        ))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Ok(ArraySerializer::new(self.scope, len, self.enter()?))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Ok(ArraySerializer::new(self.scope, len, self.enter()?))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(VariantSerializer {
+            variant,
+            inner: ArraySerializer::new(self.scope, len, self.enter()?),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(ObjectSerializer::new(self.scope, self.enter()?))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(ObjectSerializer::new(self.scope, self.enter()?))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(VariantSerializer {
+            variant,
+            inner: ObjectSerializer::new(self.scope, self.enter()?),
+        })
+    }
+}
+
+pub struct ArraySerializer<'s, 'a> {
+    scope: &'s Scope<'a>,
+    array: Handle<'a, JsArray>,
+    index: u32,
+    depth: usize,
+}
+
+impl<'s, 'a> ArraySerializer<'s, 'a> {
+    fn new(scope: &'s Scope<'a>, len: usize, depth: usize) -> Self {
+        ArraySerializer {
+            scope,
+            array: JsArray::new(scope, len as u32),
+            index: 0,
+            depth,
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        let index = self.index;
+        let value = value
+            .serialize(Serializer::new(self.scope, self.depth))
+            .map_err(|e| e.at_index(index))?;
+        self.array.set(index, value);
+        self.index += 1;
+        Ok(())
+    }
+}
+
+impl<'s, 'a> ser::SerializeSeq for ArraySerializer<'s, 'a> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = ConvertError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.array.upcast())
+    }
+}
+
+impl<'s, 'a> ser::SerializeTuple for ArraySerializer<'s, 'a> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = ConvertError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.array.upcast())
+    }
+}
+
+impl<'s, 'a> ser::SerializeTupleStruct for ArraySerializer<'s, 'a> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.array.upcast())
+    }
+}
+
+pub struct ObjectSerializer<'s, 'a> {
+    scope: &'s Scope<'a>,
+    object: Handle<'a, JsObject>,
+    // Set by `serialize_key`, used by `serialize_value`
+    key: Option<String>,
+    depth: usize,
+}
+
+impl<'s, 'a> ObjectSerializer<'s, 'a> {
+    fn new(scope: &'s Scope<'a>, depth: usize) -> Self {
+        ObjectSerializer {
+            scope,
+            object: JsObject::empty_object(scope),
+            key: None,
+            depth,
+        }
+    }
+
+    fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), ConvertError> {
+        let value = value
+            .serialize(Serializer::new(self.scope, self.depth))
+            .map_err(|e| e.at_key(key))?;
+        self.object.set(key, value);
+        Ok(())
+    }
+}
+
+impl<'s, 'a> ser::SerializeMap for ObjectSerializer<'s, 'a> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = ConvertError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ConvertError::new("serialize_value called before serialize_key"))?;
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.object.upcast())
+    }
+}
+
+impl<'s, 'a> ser::SerializeStruct for ObjectSerializer<'s, 'a> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.object.upcast())
+    }
+}
+
+/// Serializes the content of an enum variant, then tags it with the variant name.
+pub struct VariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<'s, 'a> ser::SerializeTupleVariant for VariantSerializer<ArraySerializer<'s, 'a>> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let variant = self.variant;
+        self.inner.push(value).map_err(|e| e.at_key(variant))
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(tagged(
+            self.inner.scope,
+            self.variant,
+            self.inner.array.upcast(),
+        ))
+    }
+}
+
+impl<'s, 'a> ser::SerializeStructVariant for VariantSerializer<ObjectSerializer<'s, 'a>> {
+    type Ok = Handle<'a, JsValue>;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        let variant = self.variant;
+        self.inner.insert(key, value).map_err(|e| e.at_key(variant))
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(tagged(
+            self.inner.scope,
+            self.variant,
+            self.inner.object.upcast(),
+        ))
+    }
+}
+
+/// Property names are strings, numbers and chars are converted like JS does.
+struct KeySerializer;
+
+fn key_error() -> ConvertError {
+    ConvertError::new("object keys must be strings or numbers")
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = ConvertError;
+
+    type SerializeSeq = ser::Impossible<String, ConvertError>;
+    type SerializeTuple = ser::Impossible<String, ConvertError>;
+    type SerializeTupleStruct = ser::Impossible<String, ConvertError>;
+    type SerializeTupleVariant = ser::Impossible<String, ConvertError>;
+    type SerializeMap = ser::Impossible<String, ConvertError>;
+    type SerializeStruct = ser::Impossible<String, ConvertError>;
+    type SerializeStructVariant = ser::Impossible<String, ConvertError>;
+
+    fn serialize_bool(self, v: bool) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, ConvertError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, ConvertError> {
+        Ok(v.to_owned())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_none(self) -> Result<String, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, ConvertError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, ConvertError> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, ConvertError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, ConvertError> {
+        Err(key_error())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, ConvertError> {
+        Err(key_error())
+    }
+}
@@ -0,0 +1,215 @@
+use std::mem;
+use std::os::raw::c_void;
+
+use crate::sys::scope::Scope;
+use crate::sys::types::{Handle, JsArrayBuffer, JsValue, Local, Managed, Object, Value};
+
+extern "C" {
+    fn new_typed_array(
+        out: &mut Local,
+        kind: TypedArrayKind,
+        buffer: Local,
+        byte_offset: libc::size_t,
+        length: libc::size_t,
+    ) -> bool;
+    fn value_is_typed_array(value: Local, kind: TypedArrayKind) -> bool;
+    fn array_buffer_view_contents(view: Local, data: &mut *mut c_void, len: &mut libc::size_t);
+    fn array_buffer_view_buffer(out: &mut Local, view: Local);
+}
+
+/// Must match `TypedArrayKind` in api.cpp.
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+enum TypedArrayKind {
+    Int8 = 0,
+    Uint8,
+    Uint8Clamped,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+macro_rules! typed_array {
+    ($(#[$attr:meta])* $name:ident, $elem:ty, $kind:ident, $js_name:expr) => {
+        $(#[$attr])*
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        pub struct $name(Local);
+
+        impl $name {
+            /// A view of `length` elements of `buffer`, starting at `byte_offset`.
+            ///
+            /// Panics if the view is not aligned or doesn't fit in the buffer.
+            pub fn new<'a>(
+                _scope: &Scope<'a>,
+                buffer: Handle<'a, JsArrayBuffer>,
+                byte_offset: usize,
+                length: usize,
+            ) -> Handle<'a, $name> {
+                let size = mem::size_of::<$elem>();
+                assert!(
+                    byte_offset % size == 0,
+                    "byte offset {} is not a multiple of {}",
+                    byte_offset,
+                    size
+                );
+                let end = length
+                    .checked_mul(size)
+                    .and_then(|byte_length| byte_length.checked_add(byte_offset));
+                assert!(
+                    end.filter(|&end| end <= buffer.len()).is_some(),
+                    "view of {} elements at {} is out of bounds for a buffer of {} bytes",
+                    length,
+                    byte_offset,
+                    buffer.len()
+                );
+                // Buffers made by V8 are aligned, external ones only to their elements
+                let (data, _) = buffer.contents();
+                assert!(
+                    data as usize % mem::align_of::<$elem>() == 0,
+                    "buffer at {:p} is not aligned for {}",
+                    data,
+                    stringify!($elem)
+                );
+                unsafe {
+                    let mut local: Local = mem::zeroed();
+                    new_typed_array(
+                        &mut local,
+                        TypedArrayKind::$kind,
+                        buffer.to_raw(),
+                        byte_offset,
+                        length,
+                    );
+                    Handle::new_internal($name(local))
+                }
+            }
+
+            /// An array holding a copy of `data`.
+            pub fn from_slice<'a>(scope: &Scope<'a>, data: &[$elem]) -> Handle<'a, $name> {
+                $name::from_vec(scope, data.to_vec())
+            }
+
+            /// An array backed by `data` without copying it, the vector is freed once
+            /// V8 collects the buffer.
+            pub fn from_vec<'a>(scope: &Scope<'a>, data: Vec<$elem>) -> Handle<'a, $name> {
+                let length = data.len();
+                let buffer = JsArrayBuffer::from_boxed_slice(scope, data.into_boxed_slice());
+                $name::new(scope, buffer, 0, length)
+            }
+
+            /// The number of elements.
+            pub fn len(&self) -> usize {
+                self.contents().1 / mem::size_of::<$elem>()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// The buffer the array is a view of.
+            pub fn buffer<'a>(&self, _scope: &Scope<'a>) -> Handle<'a, JsArrayBuffer> {
+                unsafe {
+                    let mut local: Local = mem::zeroed();
+                    array_buffer_view_buffer(&mut local, self.0);
+                    Handle::new_internal(JsArrayBuffer::from_raw(local))
+                }
+            }
+
+            /// The elements of the view, without copying them.
+            pub fn as_slice(&self) -> &[$elem] {
+                let (data, len) = self.contents();
+                if len == 0 {
+                    return &[];
+                }
+                unsafe { std::slice::from_raw_parts(data as *const $elem, self.len()) }
+            }
+
+            /// The elements of the view, to be changed in place.
+            ///
+            /// # Safety
+            ///
+            /// Handles are copies of each other, the caller must make sure no other
+            /// slice of the buffer or of a view on it is alive, and that JS doesn't run
+            /// meanwhile.
+            pub unsafe fn as_mut_slice(&mut self) -> &mut [$elem] {
+                let (data, len) = self.contents();
+                if len == 0 {
+                    return &mut [];
+                }
+                std::slice::from_raw_parts_mut(data as *mut $elem, self.len())
+            }
+
+            fn contents(&self) -> (*mut c_void, usize) {
+                unsafe {
+                    let mut data = std::ptr::null_mut();
+                    let mut len = 0;
+                    array_buffer_view_contents(self.0, &mut data, &mut len);
+                    (data, len)
+                }
+            }
+        }
+
+        impl Value for $name {
+            fn name() -> &'static str {
+                $js_name
+            }
+
+            fn is_typeof(value: JsValue) -> bool {
+                unsafe { value_is_typed_array(value.to_raw(), TypedArrayKind::$kind) }
+            }
+        }
+
+        impl Object for $name {}
+
+        impl Managed for $name {
+            fn to_raw(self) -> Local {
+                self.0
+            }
+
+            fn from_raw(h: Local) -> Self {
+                $name(h)
+            }
+        }
+    };
+}
+
+typed_array!(
+    /// A Javascript Int8Array.
+    JsInt8Array, i8, Int8, "Int8Array"
+);
+typed_array!(
+    /// A Javascript Uint8Array.
+    JsUint8Array, u8, Uint8, "Uint8Array"
+);
+typed_array!(
+    /// A Javascript Uint8ClampedArray, like image data.
+    JsUint8ClampedArray, u8, Uint8Clamped, "Uint8ClampedArray"
+);
+typed_array!(
+    /// A Javascript Int16Array.
+    JsInt16Array, i16, Int16, "Int16Array"
+);
+typed_array!(
+    /// A Javascript Uint16Array.
+    JsUint16Array, u16, Uint16, "Uint16Array"
+);
+typed_array!(
+    /// A Javascript Int32Array.
+    JsInt32Array, i32, Int32, "Int32Array"
+);
+typed_array!(
+    /// A Javascript Uint32Array.
+    JsUint32Array, u32, Uint32, "Uint32Array"
+);
+typed_array!(
+    /// A Javascript Float32Array.
+    JsFloat32Array, f32, Float32, "Float32Array"
+);
+typed_array!(
+    /// A Javascript Float64Array.
+    JsFloat64Array, f64, Float64, "Float64Array"
+);
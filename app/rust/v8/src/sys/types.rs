@@ -1,7 +1,9 @@
-use crate::sys::fun::FunctionCallback;
+use crate::sys::error::{CallError, TypeError};
+use crate::sys::fun::{CallbackInfo, FunctionCallback};
+use crate::sys::scope::Scope;
 use utf8_util::Utf8;
 
-use std::fmt::{Debug, Error, Formatter};
+use std::fmt::{self, Debug, Display, Error, Formatter};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::os::raw::{c_char, c_void};
@@ -13,8 +15,12 @@ extern "C" {
     /// number
     fn new_number(local: &mut Local, v: f64);
     fn number_value(local: &mut Local) -> f64;
+    /// boolean
+    fn new_boolean(local: &mut Local, v: bool);
+    fn boolean_value(local: Local) -> bool;
     /// object
     fn new_object(local: &mut Local);
+    fn context_global(local: &mut Local);
     fn object_set(out: &mut bool, obj: Local, key: Local, value: Local) -> bool;
     fn object_index_set(out: &mut bool, obj: Local, index: u32, value: Local) -> bool;
     fn object_string_set(
@@ -25,10 +31,30 @@ extern "C" {
         value: Local,
     ) -> bool;
     fn object_string_get(out: &mut Local, obj: Local, ptr: *const u8, len: u32) -> bool;
+    fn object_get(out: &mut Local, obj: Local, key: Local) -> bool;
+    fn object_index_get(out: &mut Local, obj: Local, index: u32) -> bool;
+    fn object_has(out: &mut bool, obj: Local, key: Local) -> bool;
+    fn object_index_has(out: &mut bool, obj: Local, index: u32) -> bool;
+    fn object_string_has(out: &mut bool, obj: Local, ptr: *const u8, len: u32) -> bool;
+    fn object_delete(out: &mut bool, obj: Local, key: Local) -> bool;
+    fn object_index_delete(out: &mut bool, obj: Local, index: u32) -> bool;
+    fn object_string_delete(out: &mut bool, obj: Local, ptr: *const u8, len: u32) -> bool;
+    fn object_own_property_names(out: &mut Local, obj: Local) -> bool;
     /// array
     fn new_array(local: &mut Local, len: u32);
+    fn array_length(array: Local) -> u32;
     fn new_array_buffer(local: &mut Local, data: *mut libc::c_void, byte_length: libc::size_t);
+    fn new_external_array_buffer(
+        local: &mut Local,
+        data: *mut c_void,
+        byte_length: libc::size_t,
+        free_cb: ExternalFreeCallback,
+        hint: *mut c_void,
+    );
+    fn array_buffer_contents(buffer: Local, data: &mut *mut c_void, len: &mut libc::size_t);
     fn new_utf8_string(local: &mut Local, data: *const u8, len: u32);
+    fn string_utf8_length(string: Local) -> libc::size_t;
+    fn string_write_utf8(string: Local, buf: *mut u8, capacity: libc::size_t) -> libc::size_t;
     fn function_call(
         out: &mut Local,
         local: Local,
@@ -40,41 +66,96 @@ extern "C" {
     fn null_value(out: &mut Local);
     fn undefined_value(out: &mut Local);
     fn new_function(out: &mut Local, handler: FunctionCallback);
+    fn new_closure_function(
+        out: &mut Local,
+        handler: FunctionCallback,
+        closure: *mut c_void,
+        free_cb: extern "C" fn(*mut c_void),
+    );
     fn promise_then(promise: &mut Local, handler: Local);
+    /// type checks
+    fn value_is_string(value: Local) -> bool;
+    fn value_is_number(value: Local) -> bool;
+    fn value_is_object(value: Local) -> bool;
+    fn value_is_array(value: Local) -> bool;
+    fn value_is_function(value: Local) -> bool;
+    fn value_is_promise(value: Local) -> bool;
+    fn value_is_array_buffer(value: Local) -> bool;
+    fn value_is_boolean(value: Local) -> bool;
+    fn value_is_null(value: Local) -> bool;
+    fn value_is_undefined(value: Local) -> bool;
+}
+
+type ExternalFreeCallback =
+    extern "C" fn(data: *mut c_void, byte_length: libc::size_t, hint: *mut c_void);
+
+/// Frees the boxed slice behind an external `ArrayBuffer`, `byte_length` is the size
+/// of the whole slice.
+extern "C" fn free_boxed_slice<T>(
+    data: *mut c_void,
+    byte_length: libc::size_t,
+    _hint: *mut c_void,
+) {
+    let len = byte_length / std::mem::size_of::<T>();
+    // An empty slice owns no allocation, and V8 may hand back null for it
+    if len > 0 && !data.is_null() {
+        unsafe {
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                data as *mut T,
+                len,
+            )));
+        }
+    }
 }
 
 pub trait Managed: Copy {
     fn to_raw(self) -> Local;
 
     fn from_raw(h: Local) -> Self;
-
-    fn upcast<'a>(self) -> Handle<'a, JsValue>;
 }
 
 /// A property key in Javascript object
 pub trait PropertyKey {
     unsafe fn get_from(self, out: &mut Local, obj: Local) -> bool;
     unsafe fn set_from(self, out: &mut bool, obj: Local, val: Local) -> bool;
+    unsafe fn has_in(self, out: &mut bool, obj: Local) -> bool;
+    unsafe fn delete_from(self, out: &mut bool, obj: Local) -> bool;
 }
 
 impl PropertyKey for u32 {
     unsafe fn get_from(self, out: &mut Local, obj: Local) -> bool {
-        unimplemented!()
+        object_index_get(out, obj, self)
     }
 
     unsafe fn set_from(self, out: &mut bool, obj: Local, val: Local) -> bool {
         object_index_set(out, obj, self, val)
     }
+
+    unsafe fn has_in(self, out: &mut bool, obj: Local) -> bool {
+        object_index_has(out, obj, self)
+    }
+
+    unsafe fn delete_from(self, out: &mut bool, obj: Local) -> bool {
+        object_index_delete(out, obj, self)
+    }
 }
 
 impl<'a, K: Value> PropertyKey for Handle<'a, K> {
     unsafe fn get_from(self, out: &mut Local, obj: Local) -> bool {
-        unimplemented!()
+        object_get(out, obj, self.to_raw())
     }
 
     unsafe fn set_from(self, out: &mut bool, obj: Local, val: Local) -> bool {
         object_set(out, obj, self.to_raw(), val)
     }
+
+    unsafe fn has_in(self, out: &mut bool, obj: Local) -> bool {
+        object_has(out, obj, self.to_raw())
+    }
+
+    unsafe fn delete_from(self, out: &mut bool, obj: Local) -> bool {
+        object_delete(out, obj, self.to_raw())
+    }
 }
 
 impl<'a> PropertyKey for &'a str {
@@ -87,36 +168,92 @@ impl<'a> PropertyKey for &'a str {
         let (ptr, len) = Utf8::from(self).lower();
         object_string_set(out, obj, ptr, len, val)
     }
+
+    unsafe fn has_in(self, out: &mut bool, obj: Local) -> bool {
+        let (ptr, len) = Utf8::from(self).lower();
+        object_string_has(out, obj, ptr, len)
+    }
+
+    unsafe fn delete_from(self, out: &mut bool, obj: Local) -> bool {
+        let (ptr, len) = Utf8::from(self).lower();
+        object_string_delete(out, obj, ptr, len)
+    }
 }
 
 /// The trait shared by all JavaScript values.
-pub trait Value: Managed {}
+pub trait Value: Managed {
+    /// The type name used in `TypeError`s.
+    fn name() -> &'static str;
+
+    /// Whether `value` can be downcast to this type.
+    fn is_typeof(value: JsValue) -> bool;
+}
+
+/// Rust values with a JavaScript counterpart.
+pub trait IntoHandle<'a> {
+    type Value: Value + 'a;
+
+    fn into_handle(self, scope: &Scope<'a>) -> Handle<'a, Self::Value>;
+}
 
 /// &str into Handle<JsString>
-impl<'a> Into<Handle<'a, JsString>> for &str {
-    fn into(self) -> Handle<'a, JsString> {
-        JsString::new(self)
+impl<'a> IntoHandle<'a> for &str {
+    type Value = JsString;
+
+    fn into_handle(self, scope: &Scope<'a>) -> Handle<'a, JsString> {
+        JsString::new(scope, self)
     }
 }
 
 /// i32 into Handle<JsNumber>
-impl<'a> Into<Handle<'a, JsNumber>> for i32 {
-    fn into(self) -> Handle<'a, JsNumber> {
-        JsNumber::new(self)
+impl<'a> IntoHandle<'a> for i32 {
+    type Value = JsNumber;
+
+    fn into_handle(self, scope: &Scope<'a>) -> Handle<'a, JsNumber> {
+        JsNumber::new(scope, self)
+    }
+}
+
+/// f64 into Handle<JsNumber>
+impl<'a> IntoHandle<'a> for f64 {
+    type Value = JsNumber;
+
+    fn into_handle(self, scope: &Scope<'a>) -> Handle<'a, JsNumber> {
+        JsNumber::new(scope, self)
+    }
+}
+
+/// bool into Handle<JsBoolean>
+impl<'a> IntoHandle<'a> for bool {
+    type Value = JsBoolean;
+
+    fn into_handle(self, scope: &Scope<'a>) -> Handle<'a, JsBoolean> {
+        JsBoolean::new(scope, self)
     }
 }
 
 /// Vector into Handle<JsArray>
-impl<'a> Into<Handle<'a, JsArray>> for Vec<&str> {
-    fn into(self) -> Handle<'a, JsArray> {
-        let array = JsArray::empty_array();
+impl<'a> IntoHandle<'a> for Vec<&str> {
+    type Value = JsArray;
+
+    fn into_handle(self, scope: &Scope<'a>) -> Handle<'a, JsArray> {
+        let array = JsArray::new(scope, self.len() as u32);
         for (i, e) in self.iter().enumerate() {
-            array.set_from_raw(i as u32, *e);
+            array.set_from_raw(scope, i as u32, *e);
         }
         array
     }
 }
 
+/// Handles are already JavaScript values
+impl<'a, T: Value> IntoHandle<'a> for Handle<'a, T> {
+    type Value = T;
+
+    fn into_handle(self, _scope: &Scope<'a>) -> Handle<'a, T> {
+        self
+    }
+}
+
 pub trait Object: Value {
     fn set<'a, K: PropertyKey, V: Value>(&self, key: K, val: Handle<'a, V>) {
         unsafe {
@@ -124,20 +261,55 @@ pub trait Object: Value {
             key.set_from(&mut result, self.to_raw(), val.to_raw());
         };
     }
-    fn get<'a, T: Value, K: PropertyKey>(&self, key: K) -> Handle<'a, T> {
+    /// Reads `key`, fails if a getter threw or if the property is not a `T`.
+    fn get<'a, T: Value, K: PropertyKey>(
+        &self,
+        _scope: &Scope<'a>,
+        key: K,
+    ) -> Result<Handle<'a, T>, CallError> {
+        let value = unsafe {
+            let mut out: Local = std::mem::zeroed();
+            if !key.get_from(&mut out, self.to_raw()) {
+                return Err(CallError::Threw);
+            }
+            Handle::<'a, JsValue>::new_internal(JsValue(out))
+        };
+        Ok(value.downcast()?)
+    }
+    /// Whether the object or its prototype chain has `key`.
+    fn has<K: PropertyKey>(&self, key: K) -> bool {
+        unsafe {
+            let mut result = false;
+            key.has_in(&mut result, self.to_raw()) && result
+        }
+    }
+    /// Deletes `key`, false when the property is not configurable.
+    fn delete<K: PropertyKey>(&self, key: K) -> bool {
+        unsafe {
+            let mut result = false;
+            key.delete_from(&mut result, self.to_raw()) && result
+        }
+    }
+    /// The enumerable own property names, as strings.
+    fn get_own_property_names<'a>(&self, scope: &Scope<'a>) -> Handle<'a, JsArray> {
         unsafe {
             let mut out: Local = std::mem::zeroed();
-            key.get_from(&mut out, self.to_raw());
-            Handle::new_internal(T::from_raw(out))
+            if !object_own_property_names(&mut out, self.to_raw()) {
+                return JsArray::empty_array(scope);
+            }
+            Handle::new_internal(JsArray::from_raw(out))
         }
     }
-    fn set_from_raw<'a, T: 'a, K: PropertyKey, V: Into<Handle<'a, T>>>(&self, key: K, val: V)
-    where
-        T: Value,
-    {
+    /// Sets `key` to the JavaScript counterpart of `val`.
+    fn set_from_raw<'a, K: PropertyKey, V: IntoHandle<'a>>(
+        &self,
+        scope: &Scope<'a>,
+        key: K,
+        val: V,
+    ) {
         unsafe {
             let mut result = false;
-            key.set_from(&mut result, self.to_raw(), val.into().to_raw());
+            key.set_from(&mut result, self.to_raw(), val.into_handle(scope).to_raw());
         }
     }
 }
@@ -180,6 +352,22 @@ impl<'a, T: Managed + 'a> Handle<'a, T> {
             phantom: PhantomData,
         }
     }
+
+    /// The same value, as a `JsValue` of the same scope.
+    pub fn upcast(self) -> Handle<'a, JsValue> {
+        Handle::new_internal(JsValue(self.to_raw()))
+    }
+}
+
+impl<'a> Handle<'a, JsValue> {
+    /// Casts the value to `T`, fails if it has another type.
+    pub fn downcast<T: Value + 'a>(self) -> Result<Handle<'a, T>, TypeError> {
+        if T::is_typeof(*self) {
+            Ok(Handle::new_internal(T::from_raw(self.to_raw())))
+        } else {
+            Err(TypeError::new(T::name(), self.type_name()))
+        }
+    }
 }
 
 impl<'a, T: Managed> Deref for Handle<'a, T> {
@@ -208,19 +396,86 @@ impl Managed for JsValue {
     fn from_raw(h: Local) -> Self {
         JsValue(h)
     }
-
-    fn upcast<'a>(self) -> Handle<'a, JsValue> {
-        Handle::new_internal(JsValue(self.0))
-    }
 }
 
 impl JsValue {
-    fn downcast<'a, T: Managed + 'a>(self) -> Handle<'a, T> {
-        Handle::new_internal(T::from_raw(self.to_raw()))
+    pub fn is_string(self) -> bool {
+        unsafe { value_is_string(self.0) }
+    }
+
+    pub fn is_number(self) -> bool {
+        unsafe { value_is_number(self.0) }
+    }
+
+    pub fn is_object(self) -> bool {
+        unsafe { value_is_object(self.0) }
+    }
+
+    pub fn is_array(self) -> bool {
+        unsafe { value_is_array(self.0) }
+    }
+
+    pub fn is_function(self) -> bool {
+        unsafe { value_is_function(self.0) }
+    }
+
+    pub fn is_promise(self) -> bool {
+        unsafe { value_is_promise(self.0) }
+    }
+
+    pub fn is_array_buffer(self) -> bool {
+        unsafe { value_is_array_buffer(self.0) }
+    }
+
+    pub fn is_boolean(self) -> bool {
+        unsafe { value_is_boolean(self.0) }
+    }
+
+    pub fn is_null(self) -> bool {
+        unsafe { value_is_null(self.0) }
+    }
+
+    pub fn is_undefined(self) -> bool {
+        unsafe { value_is_undefined(self.0) }
+    }
+
+    /// The most specific type name of the value, for error messages.
+    pub fn type_name(self) -> &'static str {
+        if self.is_undefined() {
+            JsUndefined::name()
+        } else if self.is_null() {
+            JsNull::name()
+        } else if self.is_string() {
+            JsString::name()
+        } else if self.is_number() {
+            JsNumber::name()
+        } else if self.is_boolean() {
+            JsBoolean::name()
+        } else if self.is_array() {
+            JsArray::name()
+        } else if self.is_function() {
+            JsFunction::<JsObject>::name()
+        } else if self.is_promise() {
+            JsPromise::<JsObject>::name()
+        } else if self.is_array_buffer() {
+            JsArrayBuffer::name()
+        } else if self.is_object() {
+            JsObject::name()
+        } else {
+            JsValue::name()
+        }
     }
 }
 
-impl Value for JsValue {}
+impl Value for JsValue {
+    fn name() -> &'static str {
+        "value"
+    }
+
+    fn is_typeof(_: JsValue) -> bool {
+        true
+    }
+}
 impl Object for JsValue {}
 
 /// A JavaScript number.
@@ -229,7 +484,7 @@ impl Object for JsValue {}
 pub struct JsNumber(Local);
 
 impl JsNumber {
-    pub fn new<'a, T: Into<f64>>(x: T) -> Handle<'a, JsNumber> {
+    pub fn new<'a, T: Into<f64>>(_scope: &Scope<'a>, x: T) -> Handle<'a, JsNumber> {
         JsNumber::new_internal(x.into())
     }
 
@@ -240,9 +495,21 @@ impl JsNumber {
             Handle::new_internal(JsNumber(local))
         }
     }
+
+    pub fn value(self) -> f64 {
+        unsafe { number_value(&mut self.to_raw()) }
+    }
 }
 
-impl Value for JsNumber {}
+impl Value for JsNumber {
+    fn name() -> &'static str {
+        "number"
+    }
+
+    fn is_typeof(value: JsValue) -> bool {
+        value.is_number()
+    }
+}
 
 impl Managed for JsNumber {
     fn to_raw(self) -> Local {
@@ -252,10 +519,6 @@ impl Managed for JsNumber {
     fn from_raw(h: Local) -> Self {
         JsNumber(h)
     }
-
-    fn upcast<'a>(self) -> Handle<'a, JsValue> {
-        Handle::new_internal(JsValue(self.0))
-    }
 }
 
 /// A JavaScript object.
@@ -264,10 +527,19 @@ impl Managed for JsNumber {
 pub struct JsObject(Local);
 
 impl JsObject {
-    pub fn empty_object<'a>() -> Handle<'a, JsObject> {
+    pub fn empty_object<'a>(_scope: &Scope<'a>) -> Handle<'a, JsObject> {
         JsObject::new_internal()
     }
 
+    /// The global object of the current context.
+    pub fn global<'a>(_scope: &Scope<'a>) -> Handle<'a, JsObject> {
+        unsafe {
+            let mut local: Local = std::mem::zeroed();
+            context_global(&mut local);
+            Handle::new_internal(JsObject(local))
+        }
+    }
+
     pub(crate) fn new_internal<'a>() -> Handle<'a, JsObject> {
         unsafe {
             let mut local: Local = std::mem::zeroed();
@@ -285,13 +557,17 @@ impl Managed for JsObject {
     fn from_raw(h: Local) -> Self {
         JsObject(h)
     }
+}
 
-    fn upcast<'a>(self) -> Handle<'a, JsValue> {
-        Handle::new_internal(JsValue(self.0))
+impl Value for JsObject {
+    fn name() -> &'static str {
+        "object"
     }
-}
 
-impl Value for JsObject {}
+    fn is_typeof(value: JsValue) -> bool {
+        value.is_object()
+    }
+}
 impl Object for JsObject {}
 
 /// A Javascript array.
@@ -300,19 +576,35 @@ impl Object for JsObject {}
 pub struct JsArray(Local);
 
 impl JsArray {
-    pub fn new<'a>(len: u32) -> Handle<'a, JsArray> {
+    pub fn new<'a>(_scope: &Scope<'a>, len: u32) -> Handle<'a, JsArray> {
         unsafe {
             let mut local: Local = std::mem::zeroed();
             new_array(&mut local, len);
             Handle::new_internal(JsArray(local))
         }
     }
-    pub fn empty_array<'a>() -> Handle<'a, JsArray> {
-        JsArray::new(0)
+    pub fn empty_array<'a>(scope: &Scope<'a>) -> Handle<'a, JsArray> {
+        JsArray::new(scope, 0)
+    }
+
+    pub fn len(self) -> u32 {
+        unsafe { array_length(self.0) }
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.len() == 0
     }
 }
 
-impl Value for JsArray {}
+impl Value for JsArray {
+    fn name() -> &'static str {
+        "array"
+    }
+
+    fn is_typeof(value: JsValue) -> bool {
+        value.is_array()
+    }
+}
 impl Object for JsArray {}
 
 impl Managed for JsArray {
@@ -323,10 +615,6 @@ impl Managed for JsArray {
     fn from_raw(h: Local) -> Self {
         JsArray(h)
     }
-
-    fn upcast<'a>(self) -> Handle<'a, JsValue> {
-        Handle::new_internal(JsValue(self.0))
-    }
 }
 
 /// A Javascript string.
@@ -335,7 +623,7 @@ impl Managed for JsArray {
 pub struct JsString(Local);
 
 impl JsString {
-    pub fn new<'a>(data: &str) -> Handle<'a, JsString> {
+    pub fn new<'a>(_scope: &Scope<'a>, data: &str) -> Handle<'a, JsString> {
         JsString::new_internal(data)
     }
 
@@ -347,9 +635,34 @@ impl JsString {
             Handle::new_internal(JsString(local))
         }
     }
+
+    /// The string as UTF-8, unpaired surrogates become U+FFFD.
+    pub fn value(self) -> String {
+        unsafe {
+            let capacity = string_utf8_length(self.0);
+            let mut buf = Vec::with_capacity(capacity);
+            let len = string_write_utf8(self.0, buf.as_mut_ptr(), capacity);
+            buf.set_len(len);
+            String::from_utf8_unchecked(buf)
+        }
+    }
 }
 
-impl Value for JsString {}
+impl Display for JsString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.value())
+    }
+}
+
+impl Value for JsString {
+    fn name() -> &'static str {
+        "string"
+    }
+
+    fn is_typeof(value: JsValue) -> bool {
+        value.is_string()
+    }
+}
 impl Object for JsString {}
 
 impl Managed for JsString {
@@ -360,10 +673,6 @@ impl Managed for JsString {
     fn from_raw(h: Local) -> Self {
         JsString(h)
     }
-
-    fn upcast<'a>(self) -> Handle<'a, JsValue> {
-        Handle::new_internal(JsValue(self.0))
-    }
 }
 
 /// A Javascript arraybuffer.
@@ -372,18 +681,92 @@ impl Managed for JsString {
 pub struct JsArrayBuffer(Local);
 
 impl JsArrayBuffer {
-    pub fn new<'a>(data: &[u8]) -> Handle<'a, JsArrayBuffer> {
+    /// A buffer holding a copy of `data`.
+    pub fn new<'a>(_scope: &Scope<'a>, data: &[u8]) -> Handle<'a, JsArrayBuffer> {
         unsafe {
             let ptr = data.as_ptr() as *mut libc::c_void;
             let mut local: Local = std::mem::zeroed();
             new_array_buffer(&mut local, ptr, data.len());
-            let _ = std::slice::from_raw_parts(ptr, data.len());
             Handle::new_internal(JsArrayBuffer(local))
         }
     }
+
+    /// A buffer backed by `data` without copying it, the vector is freed once V8
+    /// collects the buffer.
+    pub fn from_vec<'a>(scope: &Scope<'a>, data: Vec<u8>) -> Handle<'a, JsArrayBuffer> {
+        JsArrayBuffer::from_boxed_slice(scope, data.into_boxed_slice())
+    }
+
+    pub(crate) fn from_boxed_slice<'a, T: Copy>(
+        _scope: &Scope<'a>,
+        data: Box<[T]>,
+    ) -> Handle<'a, JsArrayBuffer> {
+        let byte_length = data.len() * std::mem::size_of::<T>();
+        let ptr = Box::into_raw(data) as *mut T as *mut c_void;
+        unsafe {
+            let mut local: Local = std::mem::zeroed();
+            new_external_array_buffer(
+                &mut local,
+                ptr,
+                byte_length,
+                free_boxed_slice::<T>,
+                std::ptr::null_mut(),
+            );
+            Handle::new_internal(JsArrayBuffer(local))
+        }
+    }
+
+    /// The length in bytes.
+    pub fn len(&self) -> usize {
+        self.contents().1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The bytes of the buffer, without copying them.
+    pub fn as_slice(&self) -> &[u8] {
+        let (data, len) = self.contents();
+        if len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(data as *const u8, len) }
+    }
+
+    /// The bytes of the buffer, to be changed in place.
+    ///
+    /// # Safety
+    ///
+    /// Handles are copies of each other, the caller must make sure no other slice of
+    /// the buffer or of a view on it is alive, and that JS doesn't run meanwhile.
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        let (data, len) = self.contents();
+        if len == 0 {
+            return &mut [];
+        }
+        std::slice::from_raw_parts_mut(data as *mut u8, len)
+    }
+
+    pub(crate) fn contents(&self) -> (*mut c_void, usize) {
+        unsafe {
+            let mut data = std::ptr::null_mut();
+            let mut len = 0;
+            array_buffer_contents(self.0, &mut data, &mut len);
+            (data, len)
+        }
+    }
 }
 
-impl Value for JsArrayBuffer {}
+impl Value for JsArrayBuffer {
+    fn name() -> &'static str {
+        "ArrayBuffer"
+    }
+
+    fn is_typeof(value: JsValue) -> bool {
+        value.is_array_buffer()
+    }
+}
 
 impl Managed for JsArrayBuffer {
     fn to_raw(self) -> Local {
@@ -393,10 +776,6 @@ impl Managed for JsArrayBuffer {
     fn from_raw(h: Local) -> Self {
         JsArrayBuffer(h)
     }
-
-    fn upcast<'a>(self) -> Handle<'a, JsValue> {
-        Handle::new_internal(JsValue(self.0))
-    }
 }
 
 /// A Javascript function.
@@ -408,7 +787,7 @@ pub struct JsFunction<T: Object = JsObject> {
 }
 
 impl JsFunction {
-    pub fn new<'a>(handler: FunctionCallback) -> Handle<'a, JsFunction> {
+    pub fn new<'a>(_scope: &Scope<'a>, handler: FunctionCallback) -> Handle<'a, JsFunction> {
         unsafe {
             let mut local: Local = std::mem::zeroed();
             new_function(&mut local, handler);
@@ -418,33 +797,75 @@ impl JsFunction {
             })
         }
     }
+
+    /// A function calling `f`, which is dropped once V8 collects the function.
+    pub fn from_closure<'a, F>(_scope: &Scope<'a>, f: F) -> Handle<'a, JsFunction>
+    where
+        F: Fn(&CallbackInfo) + 'static,
+    {
+        extern "C" fn call<F: Fn(&CallbackInfo)>(info: &CallbackInfo) {
+            let f = unsafe { &*(info.data() as *const F) };
+            info.catch_panic(|| f(info));
+        }
+
+        extern "C" fn free<F>(closure: *mut c_void) {
+            drop(unsafe { Box::from_raw(closure as *mut F) });
+        }
+
+        unsafe {
+            let mut local: Local = std::mem::zeroed();
+            let closure = Box::into_raw(Box::new(f)) as *mut c_void;
+            new_closure_function(&mut local, call::<F>, closure, free::<F>);
+            Handle::new_internal(JsFunction {
+                raw: local,
+                marker: PhantomData,
+            })
+        }
+    }
 }
 
 impl<CL: Object> JsFunction<CL> {
-    pub fn call<'a, 'b, T, R, A, AS>(self, this: Handle<'a, T>, args: AS) -> Handle<'a, R>
+    /// Calls the function, fails if it threw or returned something else than an `R`.
+    pub fn call<'a, 'b, 'c, T, R, A, AS>(
+        self,
+        _scope: &Scope<'a>,
+        this: Handle<'c, T>,
+        args: AS,
+    ) -> Result<Handle<'a, R>, CallError>
     where
-        T: Value + 'a,
+        T: Value + 'c,
         A: Value + 'b,
-        R: Value + 'b,
+        R: Value + 'a,
         AS: IntoIterator<Item = Handle<'b, A>>,
     {
         let mut args = args.into_iter().collect::<Vec<_>>();
-        unsafe {
+        let value = unsafe {
             let mut local: Local = std::mem::zeroed();
-            function_call(
+            if !function_call(
                 &mut local,
                 self.to_raw(),
                 this.to_raw(),
                 args.len() as u32,
                 args.as_mut_ptr() as *mut c_void,
-            );
-            Handle::new_internal(R::from_raw(local))
-        }
+            ) {
+                return Err(CallError::Threw);
+            }
+            Handle::<'a, JsValue>::new_internal(JsValue(local))
+        };
+        Ok(value.downcast()?)
     }
 }
 
 impl<T: Object> Object for JsFunction<T> {}
-impl<T: Object> Value for JsFunction<T> {}
+impl<T: Object> Value for JsFunction<T> {
+    fn name() -> &'static str {
+        "function"
+    }
+
+    fn is_typeof(value: JsValue) -> bool {
+        value.is_function()
+    }
+}
 
 impl<T: Object> Managed for JsFunction<T> {
     fn to_raw(self) -> Local {
@@ -457,10 +878,6 @@ impl<T: Object> Managed for JsFunction<T> {
             marker: PhantomData,
         }
     }
-
-    fn upcast<'a>(self) -> Handle<'a, JsValue> {
-        Handle::new_internal(JsValue(self.raw))
-    }
 }
 
 /// A Javascript promise
@@ -479,7 +896,15 @@ impl<CL: Object> JsPromise<CL> {
     }
 }
 
-impl<T: Object> Value for JsPromise<T> {}
+impl<T: Object> Value for JsPromise<T> {
+    fn name() -> &'static str {
+        "Promise"
+    }
+
+    fn is_typeof(value: JsValue) -> bool {
+        value.is_promise()
+    }
+}
 impl<T: Object> Object for JsPromise<T> {}
 
 impl<T: Object> Managed for JsPromise<T> {
@@ -493,9 +918,44 @@ impl<T: Object> Managed for JsPromise<T> {
             marker: PhantomData,
         }
     }
+}
+
+/// A Javascript boolean.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct JsBoolean(Local);
+
+impl JsBoolean {
+    pub fn new<'a>(_scope: &Scope<'a>, v: bool) -> Handle<'a, JsBoolean> {
+        unsafe {
+            let mut local: Local = std::mem::zeroed();
+            new_boolean(&mut local, v);
+            Handle::new_internal(JsBoolean(local))
+        }
+    }
+
+    pub fn value(self) -> bool {
+        unsafe { boolean_value(self.0) }
+    }
+}
+
+impl Value for JsBoolean {
+    fn name() -> &'static str {
+        "boolean"
+    }
 
-    fn upcast<'a>(self) -> Handle<'a, JsValue> {
-        Handle::new_internal(JsValue(self.raw))
+    fn is_typeof(value: JsValue) -> bool {
+        value.is_boolean()
+    }
+}
+
+impl Managed for JsBoolean {
+    fn to_raw(self) -> Local {
+        self.0
+    }
+
+    fn from_raw(h: Local) -> Self {
+        JsBoolean(h)
     }
 }
 
@@ -505,7 +965,7 @@ impl<T: Object> Managed for JsPromise<T> {
 pub struct JsNull(Local);
 
 impl JsNull {
-    pub fn new<'a>() -> Handle<'a, JsNull> {
+    pub fn new<'a>(_scope: &Scope<'a>) -> Handle<'a, JsNull> {
         JsNull::new_internal()
     }
 
@@ -518,7 +978,15 @@ impl JsNull {
     }
 }
 
-impl Value for JsNull {}
+impl Value for JsNull {
+    fn name() -> &'static str {
+        "null"
+    }
+
+    fn is_typeof(value: JsValue) -> bool {
+        value.is_null()
+    }
+}
 impl Object for JsNull {}
 
 impl Managed for JsNull {
@@ -529,10 +997,6 @@ impl Managed for JsNull {
     fn from_raw(h: Local) -> Self {
         JsNull(h)
     }
-
-    fn upcast<'a>(self) -> Handle<'a, JsValue> {
-        Handle::new_internal(JsValue(self.0))
-    }
 }
 
 /// A Javascript undefined.
@@ -541,7 +1005,7 @@ impl Managed for JsNull {
 pub struct JsUndefined(Local);
 
 impl JsUndefined {
-    pub fn new<'a>() -> Handle<'a, JsUndefined> {
+    pub fn new<'a>(_scope: &Scope<'a>) -> Handle<'a, JsUndefined> {
         JsUndefined::new_internal()
     }
 
@@ -554,7 +1018,15 @@ impl JsUndefined {
     }
 }
 
-impl Value for JsUndefined {}
+impl Value for JsUndefined {
+    fn name() -> &'static str {
+        "undefined"
+    }
+
+    fn is_typeof(value: JsValue) -> bool {
+        value.is_undefined()
+    }
+}
 impl Object for JsUndefined {}
 
 impl Managed for JsUndefined {
@@ -565,8 +1037,4 @@ impl Managed for JsUndefined {
     fn from_raw(h: Local) -> Self {
         JsUndefined(h)
     }
-
-    fn upcast<'a>(self) -> Handle<'a, JsValue> {
-        Handle::new_internal(JsValue(self.0))
-    }
 }
@@ -0,0 +1,197 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rust::runtime::isolate::Isolate;
+use v8::class::with_instance;
+use v8::convert::FromArg;
+use v8::fun::CallbackInfo;
+use v8::global::collect_garbage;
+use v8::types::*;
+use v8::ClassBuilder;
+
+struct Counter {
+    count: u32,
+    step: u32,
+}
+
+/// `new Counter(start?)`, fails on a negative start.
+fn new_counter(info: &CallbackInfo) -> Result<Counter, String> {
+    let scope = info.scope();
+    let start = info
+        .get::<JsValue>(0)
+        .and_then(|value| Option::<f64>::from_arg(&scope, value))
+        .map_err(|err| err.to_string())?
+        .unwrap_or(0.0);
+    if start < 0.0 {
+        return Err(format!("start must not be negative, got {}", start));
+    }
+    Ok(Counter {
+        count: start as u32,
+        step: 1,
+    })
+}
+
+/// An isolate with a global `Counter` class.
+fn isolate() -> Isolate {
+    let isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let counter = ClassBuilder::new(scope, "Counter", new_counter)
+            .method("increment", |counter: &mut Counter, _| {
+                counter.count += counter.step;
+                counter.count
+            })
+            .getter("count", |counter: &Counter| counter.count)
+            .accessor(
+                "step",
+                |counter: &Counter| counter.step,
+                |counter: &mut Counter, step: u32| counter.step = step,
+            )
+            .build();
+        JsObject::global(scope).set("Counter", counter);
+    });
+    isolate
+}
+
+#[test]
+fn instances_are_constructed_from_the_arguments() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "const counter = new Counter(5);
+         assert(counter instanceof Counter);
+         assert(counter.constructor.name === 'Counter', counter.constructor.name);
+         assert(counter.count === 5, counter.count);
+         assert(new Counter().count === 0);",
+    );
+}
+
+#[test]
+fn methods_getters_and_accessors_reach_the_value() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "const counter = new Counter();
+         assert(counter.increment() === 1);
+         counter.step = 10;
+         assert(counter.step === 10, counter.step);
+         assert(counter.increment() === 11);
+         assert(counter.count === 11, counter.count);
+         // Read-only, the assignment is ignored
+         counter.count = 0;
+         assert(counter.count === 11, counter.count);
+         // Instances don't share their values
+         assert(new Counter().count === 0);",
+    );
+}
+
+#[test]
+fn setters_reject_mistyped_values() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "const counter = new Counter();
+         let error;
+         try {
+             counter.step = 'ten';
+         } catch (e) {
+             error = e;
+         }
+         assert(error instanceof TypeError, error);
+         assert(counter.step === 1, counter.step);",
+    );
+}
+
+#[test]
+fn members_throw_on_foreign_receivers() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "function thrown(f) {
+             try {
+                 f();
+             } catch (e) {
+                 return e;
+             }
+             throw new Error('nothing thrown');
+         }
+         const increment = Counter.prototype.increment;
+         const count = Object.getOwnPropertyDescriptor(Counter.prototype, 'count').get;
+         for (const receiver of [{}, Object.create(Counter.prototype), new ArrayBuffer(8)]) {
+             for (const e of [thrown(() => increment.call(receiver)),
+                              thrown(() => count.call(receiver))]) {
+                 assert(e instanceof TypeError, e);
+                 assert(e.message === 'Illegal invocation', e.message);
+             }
+         }
+         const e = thrown(() => Counter());
+         assert(e instanceof TypeError, e);",
+    );
+}
+
+#[test]
+fn constructor_errors_are_thrown() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "let error;
+         try {
+             new Counter(-1);
+         } catch (e) {
+             error = e;
+         }
+         assert(error instanceof Error && !(error instanceof TypeError), error);
+         assert(error.message === 'start must not be negative, got -1', error.message);",
+    );
+}
+
+#[test]
+fn with_instance_checks_the_class() {
+    let mut isolate = isolate();
+    common::run(&mut isolate, "var counter = new Counter(3), plain = {};");
+    isolate.with_scope(|scope| {
+        let counter = common::global::<JsObject>(scope, "counter");
+        let plain = common::global::<JsObject>(scope, "plain");
+
+        assert_eq!(
+            with_instance(counter, |counter: &mut Counter| counter.count),
+            Ok(3)
+        );
+        assert!(with_instance(plain, |counter: &mut Counter| counter.count).is_err());
+        // Instances of a class of another type are rejected too
+        assert!(with_instance(counter, |_: &mut String| ()).is_err());
+    });
+}
+
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+struct Tracked;
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        DROPPED.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn values_are_dropped_with_their_instance() {
+    let mut isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let tracked = ClassBuilder::new(scope, "Tracked", |_| Ok::<_, String>(Tracked)).build();
+        JsObject::global(scope).set("Tracked", tracked);
+    });
+    common::run(
+        &mut isolate,
+        "var kept = new Tracked();
+         (() => new Tracked())();",
+    );
+    isolate.with_scope(|scope| {
+        collect_garbage(scope);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+    });
+    common::run(&mut isolate, "kept = undefined;");
+    isolate.with_scope(|scope| {
+        collect_garbage(scope);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 2);
+    });
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use v8::fun::CallbackInfo;
+use v8::global::collect_garbage;
+use v8::types::*;
+
+#[test]
+fn closures_use_their_captured_state() {
+    let mut isolate = common::isolate();
+    let calls = Rc::new(Cell::new(0));
+    isolate.with_scope(|scope| {
+        let count = JsFunction::from_closure(scope, {
+            let calls = calls.clone();
+            move |info: &CallbackInfo| {
+                calls.set(calls.get() + 1);
+                info.set_return_value(JsNumber::new(&info.scope(), calls.get()));
+            }
+        });
+        JsObject::global(scope).set("count", count);
+    });
+    common::run(
+        &mut isolate,
+        "count(); count();
+         const n = count();
+         assert(n === 3, n);",
+    );
+    assert_eq!(calls.get(), 3);
+}
+
+#[test]
+fn closure_panics_are_thrown_as_errors() {
+    let mut isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let boom = JsFunction::from_closure(scope, |_: &CallbackInfo| panic!("boom"));
+        JsObject::global(scope).set("boom", boom);
+    });
+    common::run(
+        &mut isolate,
+        "let error;
+         try {
+             boom();
+         } catch (e) {
+             error = e;
+         }
+         assert(error instanceof Error, error);
+         assert(error.message === 'Native function panicked: boom', error.message);",
+    );
+}
+
+#[test]
+fn closures_are_dropped_with_their_function() {
+    let isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let captured = Rc::new(());
+        scope.nested(|scope| {
+            let captured = captured.clone();
+            JsFunction::from_closure(scope, move |_: &CallbackInfo| {
+                let _ = &captured;
+            });
+        });
+        assert_eq!(Rc::strong_count(&captured), 2);
+
+        collect_garbage(scope);
+        assert_eq!(Rc::strong_count(&captured), 1);
+    });
+}
@@ -0,0 +1,27 @@
+//! Isolates for the integration tests, created by the app runtime which builds the
+//! C++ API.
+#![allow(dead_code)]
+
+use rust::runtime::config::RuntimeConfig;
+use rust::runtime::isolate::Isolate;
+use v8::types::{Handle, JsObject, Object, Value};
+use v8::Scope;
+
+/// A new isolate with the runtime prelude (`assert`, `console`, ...) loaded.
+pub fn isolate() -> Isolate {
+    let mut isolate = Isolate::new(RuntimeConfig::default());
+    run(&mut isolate, "");
+    isolate
+}
+
+/// Runs `source`, panics if it throws.
+pub fn run(isolate: &mut Isolate, source: &str) {
+    if let Err(err) = isolate.execute("test.js", source) {
+        panic!("{}\n{}", err, source);
+    }
+}
+
+/// The global variable `name`, panics if it is not a `T`.
+pub fn global<'a, T: Value>(scope: &Scope<'a>, name: &str) -> Handle<'a, T> {
+    JsObject::global(scope).get(scope, name).unwrap()
+}
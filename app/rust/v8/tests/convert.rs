@@ -0,0 +1,168 @@
+mod common;
+
+use serde_derive::{Deserialize, Serialize};
+use v8::error::PathSegment;
+use v8::types::*;
+use v8::{from_value, to_value};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Kind {
+    Unit,
+    Newtype(i32),
+    Tuple(u8, u8),
+    Struct { x: f64 },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Person {
+    name: String,
+    age: u32,
+    height: f64,
+    admin: bool,
+    nickname: Option<String>,
+    favorites: Vec<String>,
+    kinds: Vec<Kind>,
+    unit: (),
+}
+
+fn person() -> Person {
+    Person {
+        name: "Vampire 💖".to_owned(),
+        age: 28,
+        height: 1.82,
+        admin: false,
+        nickname: None,
+        favorites: vec!["Book".to_owned(), "Traveling".to_owned()],
+        kinds: vec![
+            Kind::Unit,
+            Kind::Newtype(-1),
+            Kind::Tuple(1, 2),
+            Kind::Struct { x: 0.5 },
+        ],
+        unit: (),
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Node {
+    next: Option<Box<Node>>,
+}
+
+/// A list of `len` nodes.
+fn chain(len: usize) -> Node {
+    (0..len).fold(Node { next: None }, |node, _| Node {
+        next: Some(Box::new(node)),
+    })
+}
+
+#[test]
+fn round_trips_through_javascript() {
+    let isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let value = to_value(scope, &person()).unwrap();
+        assert_eq!(from_value::<Person>(scope, value).unwrap(), person());
+    });
+}
+
+#[test]
+fn javascript_sees_plain_values() {
+    let mut isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let value = to_value(scope, &person()).unwrap();
+        JsObject::global(scope).set("person", value);
+    });
+    common::run(
+        &mut isolate,
+        "assert(person.name === 'Vampire 💖', person.name);
+         assert(person.nickname === null && person.unit === null);
+         assert(JSON.stringify(person.kinds) ===
+                '[\"Unit\",{\"Newtype\":-1},{\"Tuple\":[1,2]},{\"Struct\":{\"x\":0.5}}]',
+                JSON.stringify(person.kinds));",
+    );
+}
+
+#[test]
+fn reads_javascript_values() {
+    let mut isolate = common::isolate();
+    common::run(
+        &mut isolate,
+        "var person = {
+             name: 'Vampire 💖', age: 28, height: 1.82, admin: false,
+             nickname: undefined, unit: null, favorites: ['Book', 'Traveling'],
+             kinds: ['Unit', { Newtype: -1 }, { Tuple: [1, 2] }, { Struct: { x: 0.5 } }],
+         };",
+    );
+    isolate.with_scope(|scope| {
+        let value = common::global::<JsValue>(scope, "person");
+        assert_eq!(from_value::<Person>(scope, value).unwrap(), person());
+    });
+}
+
+#[test]
+fn errors_point_at_the_failing_property() {
+    #[derive(Deserialize, Debug)]
+    struct Inner {
+        #[allow(dead_code)]
+        b: Vec<u8>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Outer {
+        #[allow(dead_code)]
+        a: Inner,
+    }
+
+    let mut isolate = common::isolate();
+    common::run(
+        &mut isolate,
+        "var wrong = { a: { b: [1, 2, 'x'] } }, large = { a: { b: [256] } };",
+    );
+    isolate.with_scope(|scope| {
+        let wrong = common::global::<JsValue>(scope, "wrong");
+        let err = from_value::<Outer>(scope, wrong).unwrap_err();
+        assert_eq!(
+            err.path,
+            vec![
+                PathSegment::Key("a".to_owned()),
+                PathSegment::Key("b".to_owned()),
+                PathSegment::Index(2),
+            ]
+        );
+        assert_eq!(err.path_string(), "a.b[2]");
+        assert!(err.to_string().ends_with(" at a.b[2]"), "{}", err);
+
+        let large = common::global::<JsValue>(scope, "large");
+        let err = from_value::<Outer>(scope, large).unwrap_err();
+        assert_eq!(err.path_string(), "a.b[0]");
+        assert!(err.message.contains("256"), "{}", err);
+
+        let function = common::global::<JsValue>(scope, "assert");
+        let err = from_value::<u8>(scope, function).unwrap_err();
+        assert_eq!(err.message, "can't read a function into a Rust value");
+        assert!(err.path.is_empty());
+    });
+}
+
+#[test]
+fn cyclic_values_fail_at_the_depth_limit() {
+    let mut isolate = common::isolate();
+    common::run(&mut isolate, "var cyclic = {}; cyclic.next = cyclic;");
+    isolate.with_scope(|scope| {
+        let cyclic = common::global::<JsValue>(scope, "cyclic");
+        let err = from_value::<Node>(scope, cyclic).unwrap_err();
+        assert_eq!(
+            err.message,
+            "value nested deeper than 128 levels, it may be cyclic"
+        );
+        assert_eq!(err.path.len(), 128);
+
+        // Serializing counts options too, a node takes two levels
+        assert!(to_value(scope, &chain(63)).is_ok());
+        let err = to_value(scope, &chain(64)).unwrap_err();
+        assert_eq!(
+            err.message,
+            "value nested deeper than 128 levels, it may be cyclic"
+        );
+        assert!(err.path_string().starts_with("next.next"));
+    });
+}
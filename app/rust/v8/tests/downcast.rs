@@ -0,0 +1,84 @@
+mod common;
+
+use v8::error::TypeError;
+use v8::types::*;
+use v8::Scope;
+
+/// A value of every kind, keyed by its `type_name`.
+const VALUES: &str = "var values = {
+    undefined: undefined,
+    null: null,
+    string: 's',
+    number: 1.5,
+    boolean: true,
+    array: [1],
+    function: () => {},
+    Promise: Promise.resolve(),
+    ArrayBuffer: new ArrayBuffer(1),
+    object: {},
+};";
+
+const KINDS: &[&str] = &[
+    "undefined",
+    "null",
+    "string",
+    "number",
+    "boolean",
+    "array",
+    "function",
+    "Promise",
+    "ArrayBuffer",
+    "object",
+];
+
+/// Downcasts every value to `T`, only the kinds in `accepted` may succeed.
+fn check<T: Value>(scope: &Scope, accepted: &[&str]) {
+    let values = common::global::<JsObject>(scope, "values");
+    for kind in KINDS {
+        let value = values.get::<JsValue, _>(scope, *kind).unwrap();
+        assert_eq!(value.type_name(), *kind);
+        match value.downcast::<T>() {
+            Ok(_) => assert!(accepted.contains(kind), "{} as {}", kind, T::name()),
+            Err(err) => {
+                assert!(!accepted.contains(kind), "{} as {}", kind, T::name());
+                assert_eq!(err, TypeError::new(T::name(), kind));
+            }
+        }
+    }
+}
+
+#[test]
+fn downcast_checks_the_type() {
+    let mut isolate = common::isolate();
+    common::run(&mut isolate, VALUES);
+    isolate.with_scope(|scope| {
+        check::<JsValue>(scope, KINDS);
+        check::<JsUndefined>(scope, &["undefined"]);
+        check::<JsNull>(scope, &["null"]);
+        check::<JsString>(scope, &["string"]);
+        check::<JsNumber>(scope, &["number"]);
+        check::<JsBoolean>(scope, &["boolean"]);
+        check::<JsArray>(scope, &["array"]);
+        check::<JsFunction>(scope, &["function"]);
+        check::<JsPromise>(scope, &["Promise"]);
+        check::<JsArrayBuffer>(scope, &["ArrayBuffer"]);
+        // Arrays, functions, promises and buffers are objects too
+        check::<JsObject>(
+            scope,
+            &["array", "function", "Promise", "ArrayBuffer", "object"],
+        );
+    });
+}
+
+#[test]
+fn downcast_error_names_both_types() {
+    let isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let value = JsString::new(scope, "s").upcast();
+        let err = value.downcast::<JsNumber>().unwrap_err();
+        assert_eq!(err.to_string(), "expected number, found string");
+        // Upcasting keeps the value
+        let string = value.downcast::<JsString>().unwrap();
+        assert_eq!(string.value(), "s");
+    });
+}
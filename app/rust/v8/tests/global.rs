@@ -0,0 +1,142 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use v8::global::{collect_garbage, dispose, release_pending, set_release_notifier};
+use v8::types::*;
+use v8::{Global, Scope, Weak};
+
+/// A weak handle on a new object, the counter is bumped by its finalizer.
+fn weak_object(scope: &mut Scope, finalized: &Arc<AtomicUsize>) -> Weak<JsObject> {
+    let finalized = finalized.clone();
+    scope.nested(|scope| {
+        let object = JsObject::empty_object(scope);
+        Weak::new(scope, object, move || {
+            finalized.fetch_add(1, Ordering::SeqCst);
+        })
+    })
+}
+
+#[test]
+fn weak_handles_see_collection() {
+    let isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let finalized = Arc::new(AtomicUsize::new(0));
+        let weak = weak_object(scope, &finalized);
+        assert!(!weak.is_collected());
+
+        collect_garbage(scope);
+        assert!(weak.is_collected());
+        assert!(weak.upgrade(scope).is_none());
+        collect_garbage(scope);
+        assert_eq!(finalized.load(Ordering::SeqCst), 1);
+
+        drop(weak);
+        release_pending(scope);
+        assert_eq!(finalized.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn weak_handles_upgrade_while_reachable() {
+    let mut isolate = common::isolate();
+    common::run(&mut isolate, "var kept = { name: 'kept' };");
+    isolate.with_scope(|scope| {
+        let finalized = Arc::new(AtomicUsize::new(0));
+        let kept = common::global::<JsObject>(scope, "kept");
+        let weak = Weak::new(scope, kept, {
+            let finalized = finalized.clone();
+            move || {
+                finalized.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        collect_garbage(scope);
+
+        let upgraded = weak.upgrade(scope).unwrap();
+        assert_eq!(upgraded, kept);
+        assert_eq!(finalized.load(Ordering::SeqCst), 0);
+    });
+}
+
+#[test]
+fn finalizers_do_not_run_after_an_early_drop() {
+    let isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let finalized = Arc::new(AtomicUsize::new(0));
+        let weak = weak_object(scope, &finalized);
+        drop(weak);
+        release_pending(scope);
+
+        collect_garbage(scope);
+        assert_eq!(finalized.load(Ordering::SeqCst), 0);
+        // The finalizer and what it captured are gone
+        assert_eq!(Arc::strong_count(&finalized), 1);
+    });
+}
+
+#[test]
+fn globals_dropped_on_other_threads_are_released_on_release_pending() {
+    let isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let finalized = Arc::new(AtomicUsize::new(0));
+        let notified = Arc::new(AtomicUsize::new(0));
+        set_release_notifier(scope, {
+            let notified = notified.clone();
+            move || {
+                notified.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        let (global, weak) = scope.nested(|scope| {
+            let object = JsObject::empty_object(scope);
+            let finalized = finalized.clone();
+            let weak = Weak::new(scope, object, move || {
+                finalized.fetch_add(1, Ordering::SeqCst);
+            });
+            (Global::new(scope, object), weak)
+        });
+        assert_eq!(global.get(scope), weak.upgrade(scope).unwrap());
+
+        thread::spawn(move || drop(global)).join().unwrap();
+        assert_eq!(notified.load(Ordering::SeqCst), 1);
+        // Still held until the isolate thread releases it
+        collect_garbage(scope);
+        assert!(!weak.is_collected());
+
+        release_pending(scope);
+        collect_garbage(scope);
+        assert!(weak.is_collected());
+        assert_eq!(finalized.load(Ordering::SeqCst), 1);
+    });
+}
+
+#[test]
+fn handles_dropped_after_dispose_are_ignored() {
+    let isolate = common::isolate();
+    let (global, weak) = isolate.with_scope(|scope| {
+        let object = JsObject::empty_object(scope);
+        let global = Global::new(scope, object);
+        let weak = Weak::new(scope, object, || {});
+        dispose(scope);
+        (global, weak)
+    });
+    drop(isolate);
+    drop(global);
+    drop(weak);
+}
+
+#[test]
+#[should_panic(expected = "handle used in another isolate")]
+fn globals_can_not_move_to_another_isolate() {
+    let first = common::isolate();
+    let second = common::isolate();
+    let global = first.with_scope(|scope| {
+        let object = JsObject::empty_object(scope);
+        Global::new(scope, object)
+    });
+    second.with_scope(|scope| {
+        global.get(scope);
+    });
+}
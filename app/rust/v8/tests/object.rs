@@ -0,0 +1,101 @@
+mod common;
+
+use v8::error::{CallError, TypeError};
+use v8::fun::CallbackInfo;
+use v8::types::*;
+
+#[test]
+fn get_reads_string_index_and_handle_keys() {
+    let mut isolate = common::isolate();
+    common::run(&mut isolate, "var obj = { name: 'v8', 2: 'two' };");
+    isolate.with_scope(|scope| {
+        let obj = common::global::<JsObject>(scope, "obj");
+        let name = obj.get::<JsString, _>(scope, "name").unwrap();
+        assert_eq!(name.value(), "v8");
+        let two = obj.get::<JsString, _>(scope, 2).unwrap();
+        assert_eq!(two.value(), "two");
+        let key = JsString::new(scope, "name");
+        assert_eq!(obj.get::<JsString, _>(scope, key).unwrap(), name);
+
+        let err = obj.get::<JsNumber, _>(scope, "name").unwrap_err();
+        assert_eq!(err, CallError::Type(TypeError::new("number", "string")));
+        assert!(obj.get::<JsUndefined, _>(scope, "missing").is_ok());
+    });
+}
+
+#[test]
+fn get_fails_when_a_getter_throws() {
+    let mut isolate = common::isolate();
+    common::run(
+        &mut isolate,
+        "var obj = { get boom() { throw new Error('boom'); } };",
+    );
+    isolate.with_scope(|scope| {
+        let obj = common::global::<JsObject>(scope, "obj");
+        assert_eq!(
+            obj.get::<JsValue, _>(scope, "boom").unwrap_err(),
+            CallError::Threw
+        );
+    });
+}
+
+#[test]
+fn getter_exceptions_reach_the_caller() {
+    let mut isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let read = JsFunction::from_closure(scope, |info: &CallbackInfo| {
+            let scope = info.scope();
+            let obj = info.get::<JsObject>(0).unwrap();
+            match obj.get::<JsValue, _>(&scope, "boom") {
+                // The exception is left pending, it is thrown once the callback returns
+                Err(CallError::Threw) => {}
+                result => panic!("unexpected {:?}", result.map(|_| ())),
+            }
+        });
+        JsObject::global(scope).set("read", read);
+    });
+    common::run(
+        &mut isolate,
+        "let message;
+         try {
+             read({ get boom() { throw new Error('boom'); } });
+         } catch (e) {
+             message = e.message;
+         }
+         assert(message === 'boom', message);",
+    );
+}
+
+#[test]
+fn has_delete_and_own_property_names() {
+    let mut isolate = common::isolate();
+    common::run(
+        &mut isolate,
+        "var obj = Object.create({ inherited: 1 });
+         obj.a = 1;
+         obj[0] = 2;
+         Object.defineProperty(obj, 'fixed', { value: 3, configurable: false });
+         Object.defineProperty(obj, 'hidden', { value: 4, enumerable: false, configurable: true });",
+    );
+    isolate.with_scope(|scope| {
+        let obj = common::global::<JsObject>(scope, "obj");
+        assert!(obj.has("a"));
+        assert!(obj.has(0));
+        assert!(obj.has("inherited"));
+        assert!(!obj.has("missing"));
+
+        let names = obj.get_own_property_names(scope);
+        assert_eq!(names.len(), 2);
+        assert_eq!(names.get::<JsString, _>(scope, 0).unwrap().value(), "0");
+        assert_eq!(names.get::<JsString, _>(scope, 1).unwrap().value(), "a");
+
+        assert!(obj.delete("a"));
+        assert!(!obj.has("a"));
+        assert!(obj.delete(0));
+        assert!(obj.delete(JsString::new(scope, "hidden")));
+        assert!(!obj.delete("fixed"));
+        assert!(obj.has("fixed"));
+        // Deleting a missing property succeeds
+        assert!(obj.delete("missing"));
+    });
+}
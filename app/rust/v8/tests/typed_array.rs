@@ -0,0 +1,165 @@
+mod common;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use v8::global::collect_garbage;
+use v8::typed_array::*;
+use v8::types::*;
+
+/// Notes when the allocation at `WATCHED` is freed, to see buffers being released.
+struct WatchingAllocator;
+
+static WATCHED: AtomicUsize = AtomicUsize::new(0);
+static FREED: AtomicBool = AtomicBool::new(false);
+
+unsafe impl GlobalAlloc for WatchingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if ptr as usize == WATCHED.load(Ordering::SeqCst) {
+            FREED.store(true, Ordering::SeqCst);
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: WatchingAllocator = WatchingAllocator;
+
+#[test]
+fn vec_backed_buffers_round_trip() {
+    let mut isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let global = JsObject::global(scope);
+        let bytes = JsArrayBuffer::from_vec(scope, vec![1, 2, 3, 4]);
+        assert_eq!(bytes.as_slice(), &[1, 2, 3, 4]);
+        global.set("bytes", bytes);
+
+        let floats = JsFloat64Array::from_vec(scope, vec![0.5, -1.0]);
+        assert_eq!(floats.len(), 2);
+        assert_eq!(floats.as_slice(), &[0.5, -1.0]);
+        global.set("floats", floats);
+
+        let shorts = JsInt16Array::from_slice(scope, &[-2, 300]);
+        assert_eq!(shorts.as_slice(), &[-2, 300]);
+        global.set("shorts", shorts);
+
+        let empty = JsUint32Array::from_vec(scope, Vec::new());
+        assert!(empty.is_empty());
+        assert!(empty.as_slice().is_empty());
+        global.set("empty", empty);
+    });
+    common::run(
+        &mut isolate,
+        "assert(new Uint8Array(bytes).join() === '1,2,3,4');
+         assert(floats instanceof Float64Array && floats.join() === '0.5,-1');
+         assert(shorts instanceof Int16Array && shorts.join() === '-2,300');
+         assert(empty.length === 0);
+         new Uint8Array(bytes)[0] = 9;
+         floats[1] = 2.5;",
+    );
+    isolate.with_scope(|scope| {
+        // JS wrote to the same memory
+        let bytes = common::global::<JsArrayBuffer>(scope, "bytes");
+        assert_eq!(bytes.as_slice(), &[9, 2, 3, 4]);
+        let floats = common::global::<JsFloat64Array>(scope, "floats");
+        assert_eq!(floats.as_slice(), &[0.5, 2.5]);
+        assert_eq!(floats.buffer(scope).len(), 16);
+    });
+}
+
+#[test]
+fn vec_backed_buffers_are_freed_on_collection() {
+    let isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        scope.nested(|scope| {
+            let data = vec![7u8; 1024];
+            WATCHED.store(data.as_ptr() as usize, Ordering::SeqCst);
+            JsArrayBuffer::from_vec(scope, data);
+        });
+        assert!(!FREED.load(Ordering::SeqCst));
+        collect_garbage(scope);
+        assert!(FREED.load(Ordering::SeqCst));
+    });
+}
+
+#[test]
+fn views_cover_their_part_of_the_buffer() {
+    let mut isolate = common::isolate();
+    common::run(
+        &mut isolate,
+        "var buffer = new ArrayBuffer(8);
+         new Uint8Array(buffer).set([0, 1, 2, 3, 4, 5, 6, 7]);
+         var middle = new Uint8Array(buffer, 2, 3);
+         var words = new Uint16Array(buffer, 4);",
+    );
+    isolate.with_scope(|scope| {
+        let middle = common::global::<JsUint8Array>(scope, "middle");
+        assert_eq!(middle.len(), 3);
+        assert_eq!(middle.as_slice(), &[2, 3, 4]);
+        assert_eq!(middle.buffer(scope).len(), 8);
+
+        let words = common::global::<JsUint16Array>(scope, "words");
+        assert_eq!(words.len(), 2);
+        assert_eq!(
+            words.as_slice(),
+            &[u16::from_ne_bytes([4, 5]), u16::from_ne_bytes([6, 7])]
+        );
+
+        let buffer = common::global::<JsArrayBuffer>(scope, "buffer");
+        let tail = JsUint8Array::new(scope, buffer, 6, 2);
+        assert_eq!(tail.as_slice(), &[6, 7]);
+        let empty = JsUint8Array::new(scope, buffer, 8, 0);
+        assert!(empty.as_slice().is_empty());
+    });
+}
+
+#[test]
+#[should_panic(expected = "out of bounds")]
+fn views_past_the_buffer_panic() {
+    let isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let buffer = JsArrayBuffer::from_vec(scope, vec![0; 8]);
+        JsUint32Array::new(scope, buffer, 4, 2);
+    });
+}
+
+#[test]
+#[should_panic(expected = "not a multiple of 4")]
+fn misaligned_views_panic() {
+    let isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let buffer = JsArrayBuffer::from_vec(scope, vec![0; 8]);
+        JsFloat32Array::new(scope, buffer, 2, 1);
+    });
+}
+
+#[test]
+fn typed_arrays_check_their_kind() {
+    let mut isolate = common::isolate();
+    common::run(
+        &mut isolate,
+        "var bytes = new Uint8Array(4), clamped = new Uint8ClampedArray(4),
+             floats = new Float32Array(1), view = new DataView(new ArrayBuffer(4)),
+             array = [1, 2];",
+    );
+    isolate.with_scope(|scope| {
+        let value = |name| common::global::<JsValue>(scope, name);
+
+        assert!(value("bytes").downcast::<JsUint8Array>().is_ok());
+        assert!(value("bytes").downcast::<JsInt8Array>().is_err());
+        assert!(value("bytes").downcast::<JsUint8ClampedArray>().is_err());
+        assert!(value("clamped").downcast::<JsUint8ClampedArray>().is_ok());
+        assert!(value("clamped").downcast::<JsUint8Array>().is_err());
+        assert!(value("floats").downcast::<JsFloat32Array>().is_ok());
+        assert!(value("floats").downcast::<JsFloat64Array>().is_err());
+        for name in &["view", "array"] {
+            assert!(value(name).downcast::<JsUint8Array>().is_err());
+        }
+        let err = value("floats").downcast::<JsInt32Array>().unwrap_err();
+        assert_eq!(err.to_string(), "expected Int32Array, found object");
+    });
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use rust::runtime::isolate::Isolate;
+use v8::types::*;
+use v8_macros::v8_fn;
+
+#[v8_fn]
+fn add(a: f64, b: f64) -> f64 {
+    a + b
+}
+
+#[v8_fn]
+fn greet(name: String, greeting: Option<String>) -> String {
+    format!("{}, {}", greeting.as_deref().unwrap_or("Hello"), name)
+}
+
+#[v8_fn]
+fn port(port: u32) -> u32 {
+    port
+}
+
+#[v8_fn]
+fn fail(message: String) -> Result<(), String> {
+    Err(message)
+}
+
+#[v8_fn]
+fn explode() {
+    panic!("boom")
+}
+
+/// An isolate with the functions above as globals, and `thrown(f)` returning what `f`
+/// throws.
+fn isolate() -> Isolate {
+    let mut isolate = common::isolate();
+    isolate.with_scope(|scope| {
+        let global = JsObject::global(scope);
+        global.set("add", JsFunction::new(scope, add));
+        global.set("greet", JsFunction::new(scope, greet));
+        global.set("port", JsFunction::new(scope, port));
+        global.set("fail", JsFunction::new(scope, fail));
+        global.set("explode", JsFunction::new(scope, explode));
+    });
+    common::run(
+        &mut isolate,
+        "function thrown(f) {
+             try {
+                 f();
+             } catch (e) {
+                 return e;
+             }
+             throw new Error('nothing thrown');
+         }",
+    );
+    isolate
+}
+
+#[test]
+fn arguments_and_return_values_are_converted() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "assert(add(1, 2) === 3, add(1, 2));
+         // Extra arguments are ignored
+         assert(add(1, 2, 'extra') === 3);
+         assert(port(8080) === 8080);",
+    );
+}
+
+#[test]
+fn missing_arguments_throw_type_errors() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "let e = thrown(() => add(1));
+         assert(e instanceof TypeError, e);
+         assert(e.message === 'add() takes 2 arguments, 1 given', e.message);
+         e = thrown(() => add());
+         assert(e.message === 'add() takes 2 arguments, 0 given', e.message);",
+    );
+}
+
+#[test]
+fn mistyped_arguments_throw_type_errors() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "let e = thrown(() => add(1, '2'));
+         assert(e instanceof TypeError, e);
+         assert(e.message === 'argument 2 of add(): expected number, found string', e.message);
+         e = thrown(() => greet(null));
+         assert(e.message === 'argument 1 of greet(): expected string, found null', e.message);",
+    );
+}
+
+#[test]
+fn trailing_options_may_be_left_out() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "assert(greet('Ann') === 'Hello, Ann', greet('Ann'));
+         assert(greet('Ann', undefined) === 'Hello, Ann');
+         assert(greet('Ann', null) === 'Hello, Ann');
+         assert(greet('Ann', 'Hi') === 'Hi, Ann', greet('Ann', 'Hi'));
+         const e = thrown(() => greet());
+         assert(e.message === 'greet() takes 1 arguments, 0 given', e.message);",
+    );
+}
+
+#[test]
+fn integers_out_of_range_are_rejected() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "assert(port(2 ** 32 - 1) === 2 ** 32 - 1);
+         for (const n of [2 ** 32, -1, 1.5, NaN]) {
+             const e = thrown(() => port(n));
+             assert(e instanceof TypeError, e);
+             assert(e.message === 'argument 1 of port(): expected u32, found number', e.message);
+         }",
+    );
+}
+
+#[test]
+fn errors_and_panics_are_thrown_as_errors() {
+    let mut isolate = isolate();
+    common::run(
+        &mut isolate,
+        "let e = thrown(() => fail('failed'));
+         assert(e instanceof Error && !(e instanceof TypeError), e);
+         assert(e.message === 'failed', e.message);
+         e = thrown(() => explode());
+         assert(e instanceof Error && !(e instanceof TypeError), e);
+         assert(e.message === 'Native function panicked: boom', e.message);",
+    );
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use v8::types::*;
+
+#[test]
+fn numbers_keep_their_value() {
+    let mut isolate = common::isolate();
+    common::run(
+        &mut isolate,
+        "var values = [0, -1.5, 2 ** 53, NaN, -Infinity];",
+    );
+    isolate.with_scope(|scope| {
+        let values = common::global::<JsArray>(scope, "values");
+        let number = |index: u32| values.get::<JsNumber, _>(scope, index).unwrap().value();
+        assert_eq!(number(0), 0.0);
+        assert_eq!(number(1), -1.5);
+        assert_eq!(number(2), 9_007_199_254_740_992.0);
+        assert!(number(3).is_nan());
+        assert_eq!(number(4), f64::NEG_INFINITY);
+
+        assert_eq!(JsNumber::new(scope, 42u8).value(), 42.0);
+    });
+}
+
+#[test]
+fn strings_are_read_as_utf8() {
+    let mut isolate = common::isolate();
+    common::run(
+        &mut isolate,
+        "var values = ['', 'ascii', 'héllo wörld', '日本語', '💖', 'a\\uD800b', '\\uDC00'];",
+    );
+    isolate.with_scope(|scope| {
+        let values = common::global::<JsArray>(scope, "values");
+        let string = |index: u32| values.get::<JsString, _>(scope, index).unwrap().value();
+        assert_eq!(string(0), "");
+        assert_eq!(string(1), "ascii");
+        assert_eq!(string(2), "héllo wörld");
+        assert_eq!(string(3), "日本語");
+        assert_eq!(string(4), "💖");
+        // Unpaired surrogates can't be UTF-8
+        assert_eq!(string(5), "a\u{FFFD}b");
+        assert_eq!(string(6), "\u{FFFD}");
+
+        for s in &["", "héllo", "💖"] {
+            let handle = JsString::new(scope, s);
+            assert_eq!(handle.value(), *s);
+            assert_eq!(handle.to_string(), *s);
+        }
+    });
+}
+
+#[test]
+fn booleans_keep_their_value() {
+    let mut isolate = common::isolate();
+    common::run(&mut isolate, "var yes = true, no = false;");
+    isolate.with_scope(|scope| {
+        assert!(common::global::<JsBoolean>(scope, "yes").value());
+        assert!(!common::global::<JsBoolean>(scope, "no").value());
+        assert!(JsBoolean::new(scope, true).value());
+        assert!(!JsBoolean::new(scope, false).value());
+    });
+}
+
+#[test]
+fn array_len_counts_holes() {
+    let mut isolate = common::isolate();
+    common::run(
+        &mut isolate,
+        "var empty = [], three = [1, 2, 3], sparse = [];
+         sparse[9] = 1;",
+    );
+    isolate.with_scope(|scope| {
+        assert_eq!(common::global::<JsArray>(scope, "empty").len(), 0);
+        assert_eq!(common::global::<JsArray>(scope, "three").len(), 3);
+        assert_eq!(common::global::<JsArray>(scope, "sparse").len(), 10);
+        assert_eq!(JsArray::new(scope, 4).len(), 4);
+        assert!(JsArray::empty_array(scope).is_empty());
+    });
+}
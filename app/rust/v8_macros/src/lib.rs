@@ -1,11 +1,25 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
-use syn;
+use syn::spanned::Spanned;
 
 extern crate v8;
 #[macro_use]
 extern crate quote;
 
+/// Turns a function into a V8 function callback.
+///
+/// It either takes the raw `&CallbackInfo`, or typed arguments converted with
+/// `v8::convert::FromArg` and a return value converted with `v8::convert::IntoReturn`:
+///
+/// ```ignore
+/// #[v8_fn]
+/// pub fn add(a: f64, b: f64) -> f64 {
+///     a + b
+/// }
+/// ```
+///
+/// Missing or mistyped arguments throw a `TypeError`, an `Err` return or a panic throws
+/// an `Error`.
 #[proc_macro_attribute]
 pub fn v8_fn(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let ast = syn::parse_macro_input!(item as syn::ItemFn);
@@ -13,12 +27,110 @@ pub fn v8_fn(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let inputs = ast.decl.inputs;
     let block = ast.block;
     let vis = ast.vis;
+    let attrs = ast.attrs;
+
+    if takes_callback_info(&inputs) {
+        return (quote! {
+            #(#attrs)*
+            #[no_mangle]
+            #vis extern "C" fn #name(args: &v8::fun::CallbackInfo) {
+                args.catch_panic(|| (|#inputs|#block)(args));
+            }
+        })
+        .into();
+    }
+
+    let mut types = Vec::new();
+    for input in inputs.iter() {
+        match input {
+            syn::FnArg::Captured(arg) => types.push(arg.ty.clone()),
+            _ => {
+                return syn::Error::new(input.span(), "v8_fn arguments must have a type")
+                    .to_compile_error()
+                    .into();
+            }
+        }
+    }
+    let output = ast.decl.output;
+    let count = types.len();
+    let js_name = name.to_string();
+    let idents = (0..count)
+        .map(|i| syn::Ident::new(&format!("arg{}", i), name.span()))
+        .collect::<Vec<_>>();
+    let conversions = types
+        .iter()
+        .zip(&idents)
+        .enumerate()
+        .map(|(i, (ty, ident))| {
+            let index = i as u32;
+            let position = i + 1;
+            quote! {
+                let #ident = match args
+                    .get::<v8::types::JsValue>(#index)
+                    .and_then(|value| <#ty as v8::convert::FromArg>::from_arg(&scope, value))
+                {
+                    Ok(value) => value,
+                    Err(err) => {
+                        args.throw_type_error(&format!(
+                            "argument {} of {}(): {}",
+                            #position,
+                            #js_name,
+                            err
+                        ));
+                        return;
+                    }
+                };
+            }
+        });
+    let conversions = conversions.collect::<Vec<_>>();
 
     (quote! {
+        #(#attrs)*
         #[no_mangle]
         #vis extern "C" fn #name(args: &v8::fun::CallbackInfo) {
-            (|#inputs|#block)(args);
+            fn #name(#inputs) #output #block
+
+            args.catch_panic(|| {
+                let scope = args.scope();
+                // Trailing optional arguments may be left out
+                let optional: [bool; #count] = [#(<#types as v8::convert::FromArg>::OPTIONAL),*];
+                let required = optional.iter().rposition(|o| !o).map_or(0, |i| i + 1);
+                if (args.len() as usize) < required {
+                    args.throw_type_error(&format!(
+                        "{}() takes {} arguments, {} given",
+                        #js_name,
+                        required,
+                        args.len()
+                    ));
+                    return;
+                }
+                #(#conversions)*
+                v8::convert::IntoReturn::set_return(#name(#(#idents),*), args);
+            });
         }
     })
     .into()
 }
+
+/// Whether the function is a raw callback, taking only `&CallbackInfo`.
+fn takes_callback_info(
+    inputs: &syn::punctuated::Punctuated<syn::FnArg, syn::token::Comma>,
+) -> bool {
+    if inputs.len() != 1 {
+        return false;
+    }
+    match inputs.first().map(|pair| pair.into_value()) {
+        Some(syn::FnArg::Captured(arg)) => match arg.ty {
+            syn::Type::Reference(ref reference) => match *reference.elem {
+                syn::Type::Path(ref path) => path
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.value().ident == "CallbackInfo"),
+                _ => false,
+            },
+            _ => false,
+        },
+        _ => false,
+    }
+}
@@ -14,10 +14,12 @@ import android.widget.Toast;
 import com.node.util.JNIHelper;
 import com.node.util.ResourceUtil;
 import com.node.util.Util;
+import com.node.util.v8.Worker;
 
 @Keep
 public class MainActivity extends AppCompatActivity {
     private TextView txtMessage = null;
+    private Worker worker = null;
 
     static {
         System.loadLibrary("native-lib");
@@ -27,8 +29,6 @@ public class MainActivity extends AppCompatActivity {
 
     public native void releaseVM();
 
-    public native void demoMain();
-
     @Override
     protected void onCreate(Bundle savedInstanceState) {
         super.onCreate(savedInstanceState);
@@ -68,7 +68,8 @@ public class MainActivity extends AppCompatActivity {
             }
         });
 
-        new Thread(this::demoMain).start();
+        worker = new Worker(getAssets());
+        worker.evaluateModule("main.js");
     }
 
     @Keep
@@ -90,6 +91,7 @@ public class MainActivity extends AppCompatActivity {
 
     @Override
     protected void onDestroy() {
+        worker.terminate();
         releaseVM();
         super.onDestroy();
     }
@@ -0,0 +1,84 @@
+package com.node.util.v8;
+
+import android.content.res.AssetManager;
+import android.support.annotation.Keep;
+import android.support.annotation.Nullable;
+
+/**
+ * A JS isolate running on its own native thread. Modules are loaded from the APK assets.
+ */
+@Keep
+public class Worker {
+    private long ptr;
+
+    /**
+     * Receives the promise rejections no JS {@code unhandledrejection} listener handled,
+     * on the worker thread.
+     */
+    @Keep
+    public interface RejectionListener {
+        void onUnhandledRejection(String error);
+    }
+
+    public Worker(AssetManager assets) {
+        this(assets, null);
+    }
+
+    /**
+     * @param listener called for unhandled rejections, they are only logged when null
+     */
+    public Worker(AssetManager assets, @Nullable RejectionListener listener) {
+        ptr = createWorker(assets, listener);
+    }
+
+    /**
+     * Queues a script, it runs after the previously queued ones.
+     */
+    public void evaluate(String script) {
+        evaluate("worker.js", script);
+    }
+
+    /**
+     * Queues a script, errors and stack traces refer to it as {@code name}.
+     */
+    public synchronized void evaluate(String name, String script) {
+        if (ptr == 0) {
+            throw new IllegalStateException("Worker is terminated");
+        }
+        eval(ptr, name, script);
+    }
+
+    /**
+     * Queues the ES module at {@code specifier}, relative to the assets root.
+     */
+    public synchronized void evaluateModule(String specifier) {
+        if (ptr == 0) {
+            throw new IllegalStateException("Worker is terminated");
+        }
+        evalModule(ptr, specifier);
+    }
+
+    /**
+     * Stops running scripts and pending timers or requests, then releases the worker.
+     */
+    public synchronized void terminate() {
+        if (ptr != 0) {
+            terminate(ptr);
+            ptr = 0;
+        }
+    }
+
+    /**
+     * Sets the most verbose priority logged by the runtime, e.g. {@link android.util.Log#WARN}.
+     * Any other value turns logging off.
+     */
+    public static native void setLogLevel(int priority);
+
+    private static native long createWorker(AssetManager assets, RejectionListener listener);
+
+    private static native void eval(long ptr, String name, String script);
+
+    private static native void evalModule(long ptr, String specifier);
+
+    private static native void terminate(long ptr);
+}